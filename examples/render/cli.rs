@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+pub const BUILTIN_SCENES: &[&str] = &["random-spheres", "random-lights"];
+
+#[derive(Parser)]
+#[command(version, about = "Path traces a scene and writes the resulting image")]
+pub struct Cli {
+    /// Scene to render: a TOML scene file or a built-in scene name (random-spheres, random-lights)
+    #[arg(default_value = "random-spheres")]
+    pub scene: String,
+
+    /// File to write the image to [default: standard output]
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Image format [default: inferred from the output extension, ppm for standard output]
+    #[arg(short, long, value_enum)]
+    pub format: Option<Format>,
+
+    /// Image width in pixels
+    #[arg(long, value_parser = clap::value_parser!(i64).range(1..))]
+    pub width: Option<i64>,
+
+    /// Image height in pixels; with --width this also sets the aspect ratio
+    #[arg(long, value_parser = clap::value_parser!(i64).range(1..))]
+    pub height: Option<i64>,
+
+    /// Samples per pixel
+    #[arg(short, long, value_parser = clap::value_parser!(i64).range(1..))]
+    pub spp: Option<i64>,
+
+    /// Maximum number of bounces per path
+    #[arg(long, value_parser = clap::value_parser!(i64).range(1..))]
+    pub max_depth: Option<i64>,
+
+    /// Number of worker threads [default: one per logical CPU]
+    #[arg(short = 'j', long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub threads: Option<u16>,
+
+    /// Seed for the random number generator, for reproducible renders
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Light transport algorithm [default: the scene's, or path]
+    #[arg(long, value_enum)]
+    pub integrator: Option<IntegratorKind>,
+
+    /// How to choose which light to sample at each bounce
+    #[arg(long, value_enum, default_value_t = LightSampler::Bvh)]
+    pub light_sampler: LightSampler,
+
+    /// Bits per channel: 8 or 16 for PNG [default: 8], 16 (half) or 32 (float) for EXR [default: 16]
+    #[arg(long, value_parser = ["8", "16", "32"])]
+    pub bit_depth: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Ppm,
+    Png,
+    /// OpenEXR, linear
+    Exr,
+    /// Radiance RGBE, linear
+    Hdr,
+    /// Portable Float Map, linear
+    Pfm,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IntegratorKind {
+    /// Path tracing with light sampling and MIS
+    Path,
+    /// Path tracing with material sampling only
+    Naive,
+    /// Bidirectional path tracing, for caustics and hard-to-reach lights
+    Bdpt,
+    /// Direct light plus mirror and glass recursion
+    Whitted,
+    /// Ambient occlusion, unlimited distance
+    AmbientOcclusion,
+    /// Surface normals
+    Normals,
+    /// Surface albedo
+    Albedo,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LightSampler {
+    /// Favour lights by power, distance and orientation through a light BVH
+    Bvh,
+    /// Favour lights by power alone
+    Power,
+    /// Every light equally
+    Uniform,
+}
+
+impl Format {
+    fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ppm" => Some(Format::Ppm),
+            "png" => Some(Format::Png),
+            "exr" => Some(Format::Exr),
+            "hdr" => Some(Format::Hdr),
+            "pfm" => Some(Format::Pfm),
+            _ => None,
+        }
+    }
+}
+
+impl Cli {
+    // Resolves the output format from --format and the output extension,
+    // rejecting combinations that disagree.
+    pub fn output_format(&self) -> Result<Format, String> {
+        let extension = self.output.as_ref().and_then(|path| path.extension()).and_then(|ext| ext.to_str());
+        let inferred = extension.and_then(Format::from_extension);
+
+        match (self.format, extension, inferred) {
+            (Some(format), Some(ext), Some(inferred)) if format != inferred => Err(format!(
+                "--format {} conflicts with the output extension '.{}'",
+                format.to_possible_value().unwrap().get_name(),
+                ext
+            )),
+            (Some(format), _, _) => Ok(format),
+            (None, _, Some(inferred)) => Ok(inferred),
+            (None, Some(ext), None) => Err(format!("cannot infer an image format from '.{}'; pass --format", ext)),
+            (None, None, None) => Ok(Format::Ppm),
+        }
+    }
+
+    // Bits per channel for formats that offer a choice; PPM, HDR and PFM
+    // have a fixed layout and reject --bit-depth.
+    pub fn bit_depth(&self, format: Format) -> Result<u8, String> {
+        let requested: Option<u8> = self.bit_depth.as_ref().map(|depth| depth.parse().unwrap());
+        match (format, requested) {
+            (Format::Png, None) => Ok(8),
+            (Format::Png, Some(depth @ (8 | 16))) => Ok(depth),
+            (Format::Exr, None) => Ok(16),
+            (Format::Exr, Some(depth @ (16 | 32))) => Ok(depth),
+            (_, None) => Ok(0),
+            (format, Some(depth)) => Err(format!(
+                "--bit-depth {} is not supported for {} output",
+                depth,
+                format.to_possible_value().unwrap().get_name()
+            )),
+        }
+    }
+}
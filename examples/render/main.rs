@@ -0,0 +1,169 @@
+mod cli;
+
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path, process, sync::Arc};
+use clap::{CommandFactory, Parser, error::ErrorKind};
+use raytracer::{
+    output::{write_exr, write_hdr, write_pfm, write_png, write_ppm, ExrLayer, ExrPrecision},
+    random_double, random_double_x, scene::{load_scene, Scene}, seed_thread_rng, sphere::Sphere,
+    AmbientOcclusion, AreaLight, Background, BidirectionalPathTracer, BvhNode, Camera, CameraSettings, DebugChannel, DebugIntegrator, Dielectric, HittableList, Lambertian,
+    Light, LightBvh, LightList, Material, Metal, NaivePathTracer, PathTracer, PowerLightSampler, Vec3, Whitted,
+};
+use crate::cli::{Cli, Format, IntegratorKind, LightSampler, BUILTIN_SCENES};
+
+fn main(){
+    let cli = Cli::parse();
+    let format = cli.output_format()
+        .unwrap_or_else(|message| Cli::command().error(ErrorKind::ArgumentConflict, message).exit());
+    let bit_depth = cli.bit_depth(format)
+        .unwrap_or_else(|message| Cli::command().error(ErrorKind::ArgumentConflict, message).exit());
+
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads as usize).build_global()
+            .unwrap_or_else(|err| fail(&err));
+    }
+    if let Some(seed) = cli.seed {
+        seed_thread_rng(seed);
+    }
+
+    let scene = match cli.scene.as_str() {
+        "random-spheres" => random_spheres(false),
+        "random-lights" => random_spheres(true),
+        path => load_scene(Path::new(path)).unwrap_or_else(|err| {
+            if !Path::new(path).exists() {
+                fail(&format!("no scene file or built-in scene named '{}' (built-in scenes: {})", path, BUILTIN_SCENES.join(", ")))
+            }
+            fail(&err)
+        }),
+    };
+
+    for warning in &scene.warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    let mut camera = scene.camera;
+    match (cli.width, cli.height) {
+        (Some(width), Some(height)) => {
+            camera.image_width = width;
+            camera.aspect_ratio = width as f64 / height as f64;
+        }
+        (Some(width), None) => camera.image_width = width,
+        (None, Some(height)) => camera.image_width = ((height as f64 * camera.aspect_ratio).round() as i64).max(1),
+        (None, None) => {}
+    }
+    if let Some(spp) = cli.spp {
+        camera.samples_per_pixel = spp;
+    }
+    if let Some(max_depth) = cli.max_depth {
+        camera.max_depth = max_depth;
+    }
+    camera.seed = cli.seed;
+    if let Some(kind) = cli.integrator {
+        camera.integrator = match kind {
+            IntegratorKind::Path => Box::new(PathTracer),
+            IntegratorKind::Naive => Box::new(NaivePathTracer),
+            IntegratorKind::Bdpt => Box::new(BidirectionalPathTracer),
+            IntegratorKind::Whitted => Box::new(Whitted),
+            IntegratorKind::AmbientOcclusion => Box::new(AmbientOcclusion::new(f64::INFINITY)),
+            IntegratorKind::Normals => Box::new(DebugIntegrator::new(DebugChannel::Normals)),
+            IntegratorKind::Albedo => Box::new(DebugIntegrator::new(DebugChannel::Albedo)),
+        };
+    }
+
+    let mut out: Box<dyn Write> = match &cli.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)
+            .unwrap_or_else(|err| fail(&format!("{}: {}", path.display(), err))))),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let world = BvhNode::new(scene.world);
+    let lights: Box<dyn Light> = match cli.light_sampler {
+        LightSampler::Bvh => Box::new(LightBvh::new(scene.lights)),
+        LightSampler::Power => Box::new(PowerLightSampler::new(scene.lights)),
+        LightSampler::Uniform => Box::new(scene.lights),
+    };
+    let image = camera.render(&world, lights.as_ref());
+
+    let result = match format {
+        Format::Ppm => write_ppm(&mut out, &image),
+        Format::Png => write_png(&mut out, &image, bit_depth).and_then(|_| out.flush()),
+        Format::Exr => {
+            let precision = if bit_depth == 32 { ExrPrecision::Float } else { ExrPrecision::Half };
+            write_exr(&mut out, &[ExrLayer { name: "", image: &image }], precision)
+        }
+        Format::Hdr => write_hdr(&mut out, &image),
+        Format::Pfm => write_pfm(&mut out, &image),
+    };
+    result.unwrap_or_else(|err| fail(&err));
+}
+
+fn fail(err: &dyn std::fmt::Display) -> ! {
+    eprintln!("error: {}", err);
+    process::exit(1);
+}
+
+// With `lights`, about a third of the small spheres glow instead and the
+// scene is lit by them alone.
+fn random_spheres(lights: bool) -> Scene {
+    let mut world = HittableList::new();
+    let mut light_list = LightList::new();
+
+    let ground_material = Arc::new(Lambertian::new(Vec3::new(0.5,0.5,0.5)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0,-1000.0,0.0), 1000.0, ground_material)));
+
+    for a in -11..11{
+        for b in -11..11{
+            let choose_mat = random_double();
+            let center = Vec3::new(a as f64 + 0.9 * random_double(),0.2,b as f64 + 0.9 * random_double());
+
+            if (center - Vec3::new(4.0,0.2,0.0)).length() > 0.9 {
+                let sphere_material: Arc<dyn Material>;
+
+                if lights && choose_mat < 0.35 {
+                    let color = Vec3::new(0.3, 0.3, 0.3) + Vec3::random_vector1().scalar_mul(0.7);
+                    let light = AreaLight::sphere(center, 0.2, color, random_double_x(2.0, 20.0));
+                    world.add(light.shape.clone());
+                    light_list.add(Arc::new(light));
+                } else if choose_mat < 0.8 {
+                    let albedo = Vec3::random_vector1() * Vec3::random_vector1();
+                    sphere_material = Arc::new(Lambertian::new(albedo));
+                    world.add(Arc::new(Sphere::new(center,0.2,sphere_material)));   
+                } else if choose_mat <0.95 {
+                    let albedo = Vec3::random_vector1() * Vec3::random_vector1();
+                    let fuzz = random_double();
+                    sphere_material = Arc::new(Metal::new(albedo,fuzz));
+                    world.add(Arc::new(Sphere::new(center,0.2,sphere_material)));   
+                } else{
+                    sphere_material = Arc::new(Dielectric::new(1.5));
+                    world.add(Arc::new(Sphere::new(center,0.2,sphere_material)));   
+                }
+            }
+        }
+    }
+
+    let material1 = Arc::new(Dielectric::new(1.5));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0,1.0,0.0),1.0,material1)));   
+
+    let material2 = Arc::new(Lambertian::new(Vec3::new(0.4,0.2,0.1)));
+    world.add(Arc::new(Sphere::new(Vec3::new(-4.0,1.0,0.0),1.0,material2))); 
+
+    let material3 = Arc::new(Metal::new(Vec3::new(0.7,0.6,0.5),0.0));
+    world.add(Arc::new(Sphere::new(Vec3::new(4.0,1.0,0.0),1.0,material3))); 
+
+    
+    let mut camera = Camera::new(CameraSettings {
+        aspect_ratio: 16.0/9.0,
+        image_width: 1200,
+        samples_per_pixel: 500,
+        max_depth: 50,
+        vfov: 20,
+        lookfrom: Vec3::new(13.0,2.0,3.0),
+        lookat: Vec3::new(0.0,0.0,0.0),
+        vup: Vec3::new(0.0,1.0,0.0),
+        defocus_angle: 0.6,
+        focus_dist: 10.0,
+    });
+    if lights {
+        camera.background = Background::black();
+    }
+
+    Scene { camera, world, lights: light_list, warnings: Vec::new() }
+}
\ No newline at end of file
@@ -0,0 +1,105 @@
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::vec3::Point3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb{
+    pub x : Interval,
+    pub y : Interval,
+    pub z : Interval,
+}
+
+impl Aabb{
+    pub const EMPTY: Aabb = Aabb::new(Interval::EMPTY, Interval::EMPTY, Interval::EMPTY);
+    pub const UNIVERSE: Aabb = Aabb::new(Interval::UNIVERSE, Interval::UNIVERSE, Interval::UNIVERSE);
+
+    pub const fn new(x: Interval, y: Interval, z: Interval) -> Aabb {
+        Aabb { x, y, z }
+    }
+
+    pub fn from_points(a: Point3, b: Point3) -> Aabb {
+        let mut bbox = Aabb::new(
+            Interval::new(a.x().min(b.x()), a.x().max(b.x())),
+            Interval::new(a.y().min(b.y()), a.y().max(b.y())),
+            Interval::new(a.z().min(b.z()), a.z().max(b.z())),
+        );
+        bbox.pad_to_minimums();
+        bbox
+    }
+
+    pub fn enclosing(a: &Aabb, b: &Aabb) -> Aabb {
+        Aabb::new(
+            Interval::enclosing(&a.x, &b.x),
+            Interval::enclosing(&a.y, &b.y),
+            Interval::enclosing(&a.z, &b.z),
+        )
+    }
+
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, mut ray_t: Interval) -> bool {
+        let origin = r.origin();
+        let direction = r.direction();
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / direction.e[axis];
+
+            let t0 = (ax.min - origin.e[axis]) * adinv;
+            let t1 = (ax.max - origin.e[axis]) * adinv;
+
+            let (t_near, t_far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if t_near > ray_t.min {
+                ray_t.min = t_near;
+            }
+            if t_far < ray_t.max {
+                ray_t.max = t_far;
+            }
+
+            if ray_t.max <= ray_t.min {
+                return false
+            }
+        }
+        true
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let dx = self.x.size().max(0.0);
+        let dy = self.y.size().max(0.0);
+        let dz = self.z.size().max(0.0);
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    pub fn centroid(&self, axis: usize) -> f64 {
+        self.axis_interval(axis).center()
+    }
+
+    fn pad_to_minimums(&mut self) {
+        let delta = 0.0001;
+        if self.x.size() < delta { self.x = self.x.expand(delta); }
+        if self.y.size() < delta { self.y = self.y.expand(delta); }
+        if self.z.size() < delta { self.z = self.z.expand(delta); }
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use crate::environment::EnvironmentMap;
+use crate::ray::Ray;
+use crate::sky::Sky;
+use crate::vec3::Vec3;
+
+// Radiance returned for rays that escape the scene.
+#[derive(Clone, Debug)]
+pub enum Background{
+    Solid(Vec3),
+    // Blends from `bottom` (looking straight down) to `top` (straight up).
+    Gradient { bottom: Vec3, top: Vec3 },
+    // Image-based lighting. Add the same map to the camera's lights so it is
+    // importance sampled.
+    Environment(Arc<EnvironmentMap>),
+    // Physical daylight sky. Add its `sun` to the camera's lights.
+    Sky(Arc<Sky>),
+}
+
+impl Background{
+    pub fn black() -> Background {
+        Background::Solid(Vec3::new(0.0, 0.0, 0.0))
+    }
+
+    pub fn sky() -> Background {
+        Background::Gradient { bottom: Vec3::new(1.0, 1.0, 1.0), top: Vec3::new(0.5, 0.7, 1.0) }
+    }
+
+    pub fn value(&self, r: &Ray) -> Vec3 {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let unit_direction = (r.direction()).unit_vector();
+                let a = (unit_direction.y() + 1.0 ) * (0.5);
+                bottom.scalar_mul(1.0-a) + top.scalar_mul(a)
+            }
+            Background::Environment(map) => map.value(r.direction()),
+            Background::Sky(sky) => sky.value(r.direction()),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::sky()
+    }
+}
@@ -0,0 +1,610 @@
+use std::f64::consts::PI;
+
+use crate::hittable::HitRecord;
+use crate::integrator::{black, hit, power_heuristic, Integrator, RenderContext};
+use crate::interval::Interval;
+use crate::light::{LightSample, LightSource};
+use crate::material::{ScatterDirection, ScatterRecord};
+use crate::onb::Onb;
+use crate::pdf::random_cosine_direction;
+use crate::random_double;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// Bidirectional path tracing (Veach, 1997). Each sample traces one path
+// from the camera and one from a light, then joins every prefix of one to
+// every prefix of the other, weighting the strategies that could have made
+// the same path by the balance heuristic. Light paths joined straight to
+// the lens are splatted onto whatever pixel they land in, which is how
+// caustics seen through glass get rendered at all.
+//
+// Only lights with a position start light paths. Light from infinity
+// (environment maps, the sun, directional lights) is gathered by the camera
+// path as in `PathTracer`.
+pub struct BidirectionalPathTracer;
+
+enum VertexKind<'a> {
+    Camera,
+    // The start of a light path, or a point picked on a light to join a
+    // camera path to.
+    Light(LightSource<'a>),
+    // `r_in` is the ray that arrived; `srec` is None where nothing scatters.
+    Surface { rec: HitRecord, r_in: Ray, srec: Option<ScatterRecord> },
+}
+
+struct Vertex<'a> {
+    kind: VertexKind<'a>,
+    p: Point3,
+    // Needed to turn densities into densities per unit area; None at the
+    // lens and at point lights.
+    normal: Option<Vec3>,
+    // The path's contribution up to here over the density of sampling it.
+    beta: Vec3,
+    // Scattered specularly, so the path can't be joined here.
+    delta: bool,
+    // Densities per unit area of reaching this vertex from the previous
+    // one on its own path, and from the next one going the other way.
+    pdf_fwd: f64,
+    pdf_rev: f64,
+}
+
+impl<'a> Vertex<'a> {
+    fn light(point_pdf: f64, p: Point3, source: LightSource<'a>) -> Vertex<'a> {
+        let normal = match &source {
+            LightSource::Surface(rec) => Some(rec.normal),
+            LightSource::Point(_) => None,
+        };
+        let beta = Vec3::new(1.0, 1.0, 1.0).scalar_div(point_pdf);
+        Vertex { kind: VertexKind::Light(source), p, normal, beta, delta: false, pdf_fwd: point_pdf, pdf_rev: 0.0 }
+    }
+
+    fn connectible(&self) -> bool {
+        match &self.kind {
+            VertexKind::Camera | VertexKind::Light(_) => true,
+            VertexKind::Surface { srec, .. } => {
+                matches!(srec, Some(ScatterRecord { direction: ScatterDirection::Sampled(_), .. }))
+            }
+        }
+    }
+
+    fn is_point_light(&self) -> bool {
+        matches!(self.kind, VertexKind::Light(LightSource::Point(_)))
+    }
+
+    // The BSDF times the cosine for light between the path so far and
+    // `next`, which is symmetric for the materials here.
+    fn f(&self, next: &Vertex) -> Vec3 {
+        let VertexKind::Surface { rec, r_in, srec: Some(srec) } = &self.kind else {
+            return black()
+        };
+        let scattered = Ray::with_time(self.p, next.p - self.p, r_in.time());
+        rec.mat.eval(r_in, rec, srec, &scattered)
+    }
+
+    // Light leaving a light vertex towards `to`: radiance times the cosine
+    // on surfaces, intensity at point lights.
+    fn emission(&self, to: Point3) -> Vec3 {
+        let VertexKind::Light(source) = &self.kind else {
+            return black()
+        };
+        let direction = (to - self.p).unit_vector();
+        match source {
+            LightSource::Surface(rec) => {
+                let from_viewer = Ray::new(self.p + direction, direction.scalar_mul(-1.0));
+                let mut rec = rec.clone();
+                rec.set_face_normal(&from_viewer, self.normal.unwrap());
+                let cosine = Vec3::dot_product(rec.normal, direction).abs();
+                rec.mat.emitted(&from_viewer, &rec).scalar_mul(cosine)
+            }
+            LightSource::Point(light) => light.intensity(direction),
+        }
+    }
+
+    // Turns the solid-angle density of going from here towards `next` into
+    // a density per unit area at `next`.
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let distance_squared = w.length_squared();
+        if distance_squared <= 0.0 {
+            return 0.0
+        }
+        let mut pdf = pdf / distance_squared;
+        if let Some(normal) = next.normal {
+            pdf *= Vec3::dot_product(normal, w).abs() / distance_squared.sqrt();
+        }
+        pdf
+    }
+
+    // Density per unit area of this vertex sampling `next`, given that the
+    // path arrived from `prev`.
+    fn pdf(&self, prev: Option<&Vertex>, next: &Vertex, ctx: &RenderContext) -> f64 {
+        let direction = next.p - self.p;
+        let pdf = match &self.kind {
+            VertexKind::Camera => ctx.camera.direction_pdf(direction),
+            VertexKind::Light(_) => return self.pdf_light(next),
+            VertexKind::Surface { rec, r_in, .. } => match prev {
+                Some(prev) => scattering_pdf(rec, prev.p, direction, r_in.time()),
+                None => 0.0,
+            },
+        };
+        self.convert_density(pdf, next)
+    }
+
+    // Density per unit area at `next` of a light path leaving this vertex,
+    // which is on a light, towards it.
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let direction = (next.p - self.p).unit_vector();
+        let pdf = match self.normal {
+            Some(normal) => emission_direction_pdf(normal, direction),
+            None => 1.0 / (4.0 * PI),
+        };
+        self.convert_density(pdf, next)
+    }
+}
+
+impl BidirectionalPathTracer {
+    // The camera path, plus the light from infinity it gathers on the way.
+    fn camera_path<'a>(&self, r: &Ray, ctx: &RenderContext<'a>, max_vertices: usize) -> (Vec<Vertex<'a>>, Vec3) {
+        let mut path = vec![Vertex {
+            kind: VertexKind::Camera,
+            p: r.origin(),
+            normal: None,
+            beta: Vec3::new(1.0, 1.0, 1.0),
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }];
+        let pdf = ctx.camera.direction_pdf(r.direction());
+        let mut infinite = black();
+        let ray = Ray::with_time(r.origin(), r.direction(), r.time());
+        random_walk(ray, Vec3::new(1.0, 1.0, 1.0), pdf, max_vertices, &mut path, ctx, Some(&mut infinite));
+        (path, infinite)
+    }
+
+    fn light_path<'a>(&self, time: f64, ctx: &RenderContext<'a>, max_vertices: usize) -> Vec<Vertex<'a>> {
+        let mut path = Vec::new();
+        if max_vertices == 0 {
+            return path
+        }
+        let Some(point) = ctx.lights.sample_point() else {
+            return path
+        };
+        if point.pdf <= 0.0 {
+            return path
+        }
+        let (direction, pdf_dir) = match &point.source {
+            LightSource::Surface(rec) => {
+                // Either side: `emission` is zero where the light doesn't emit.
+                let normal = if random_double() < 0.5 { rec.normal } else { rec.normal.scalar_mul(-1.0) };
+                let direction = Onb::new(normal).transform(random_cosine_direction());
+                (direction, emission_direction_pdf(normal, direction))
+            }
+            LightSource::Point(_) => (Vec3::random_unit_vector(), 1.0 / (4.0 * PI)),
+        };
+        let vertex = Vertex::light(point.pdf, point.p, point.source);
+        let emitted = vertex.emission(vertex.p + direction);
+        let beta = (vertex.beta * emitted).scalar_div(pdf_dir);
+        let ray = Ray::with_time(vertex.p, direction, time);
+        path.push(vertex);
+        if pdf_dir > 0.0 && !emitted.near_zero() {
+            random_walk(ray, beta, pdf_dir, max_vertices, &mut path, ctx, None);
+        }
+        path
+    }
+
+    // The light carried by the path of the first `s` light vertices and the
+    // first `t` camera vertices, for t >= 2, MIS-weighted.
+    fn connect(&self, light_path: &[Vertex], camera_path: &[Vertex], s: usize, t: usize, time: f64, ctx: &RenderContext) -> Vec3 {
+        let pt = &camera_path[t - 1];
+        let mut sampled = None;
+        let color = if s == 0 {
+            let VertexKind::Surface { rec, r_in, .. } = &pt.kind else {
+                return black()
+            };
+            pt.beta * rec.mat.emitted(r_in, rec)
+        } else if s == 1 {
+            if !pt.connectible() {
+                return black()
+            }
+            // A fresh point on a light rather than the light path's first.
+            let Some(point) = ctx.lights.sample_point() else {
+                return black()
+            };
+            if point.pdf <= 0.0 {
+                return black()
+            }
+            let light = sampled.insert(Vertex::light(point.pdf, point.p, point.source));
+            let color = pt.beta * pt.f(light) * (light.emission(pt.p) * light.beta);
+            joined(color, pt.p, light.p, time, ctx)
+        } else {
+            let qs = &light_path[s - 1];
+            if !qs.connectible() || !pt.connectible() {
+                return black()
+            }
+            let color = qs.beta * qs.f(pt) * (pt.f(qs) * pt.beta);
+            joined(color, qs.p, pt.p, time, ctx)
+        };
+        if color.near_zero() {
+            return black()
+        }
+        color.scalar_mul(mis_weight(light_path, camera_path, sampled.as_ref(), s, t, ctx))
+    }
+
+    // The path of the first `s` light vertices joined straight to the lens,
+    // as a pixel and the light to add there.
+    fn connect_to_camera(&self, light_path: &[Vertex], s: usize, time: f64, ctx: &RenderContext) -> Option<((usize, usize), Vec3)> {
+        let qs = &light_path[s - 1];
+        if !qs.connectible() {
+            return None
+        }
+        let lens = ctx.camera.sample_lens(qs.p)?;
+        let camera = Vertex {
+            kind: VertexKind::Camera,
+            p: lens.origin,
+            normal: None,
+            beta: Vec3::new(1.0, 1.0, 1.0),
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        };
+        let color = joined((qs.beta * qs.f(&camera)).scalar_mul(lens.importance), qs.p, camera.p, time, ctx);
+        if color.near_zero() {
+            return None
+        }
+        let weight = mis_weight(light_path, &[], Some(&camera), s, 1, ctx);
+        Some((lens.pixel, color.scalar_mul(weight)))
+    }
+}
+
+impl Integrator for BidirectionalPathTracer {
+    fn radiance(&self, r: &Ray, ctx: &RenderContext) -> Vec3 {
+        // As in the other integrators, a path has at most `max_depth` rays.
+        let max_depth = ctx.max_depth.max(0) as usize;
+        if max_depth == 0 {
+            return black()
+        }
+        let (camera_path, mut color) = self.camera_path(r, ctx, max_depth + 1);
+        let light_path = self.light_path(r.time(), ctx, max_depth);
+
+        for t in 1..=camera_path.len() {
+            // s = 1 samples its own light point, so it runs even when no
+            // light path could start.
+            for s in 0..=light_path.len().max(1) {
+                // A light vertex seen straight from the lens is left to
+                // camera paths, which find it as (0, 2).
+                if s + t < 2 || s + t - 1 > max_depth || (s == 1 && t == 1) {
+                    continue
+                }
+                if t == 1 {
+                    if let Some((pixel, splat)) = self.connect_to_camera(&light_path, s, r.time(), ctx) {
+                        ctx.splats.add(pixel, splat);
+                    }
+                } else {
+                    color = color + self.connect(&light_path, &camera_path, s, t, r.time(), ctx);
+                }
+            }
+        }
+        color
+    }
+}
+
+// Extends `path` by sampling the materials from `ray` on, until it has
+// `max_vertices` vertices or escapes. `pdf` is the solid-angle density of
+// `ray`. Camera paths pass `infinite` to gather light from infinity.
+fn random_walk(
+    mut ray: Ray,
+    mut beta: Vec3,
+    mut pdf: f64,
+    max_vertices: usize,
+    path: &mut Vec<Vertex>,
+    ctx: &RenderContext,
+    mut infinite: Option<&mut Vec3>,
+) {
+    // The density of the bounce that picked `ray`, for MIS against light
+    // sampling; None for camera rays and specular bounces.
+    let mut bsdf_pdf = None;
+    while path.len() < max_vertices {
+        let Some(rec) = hit(ctx.world, &ray) else {
+            if let Some(infinite) = infinite.as_deref_mut() {
+                let mut emitted = ctx.background.value(&ray);
+                if let Some(bsdf_pdf) = bsdf_pdf && !emitted.near_zero() {
+                    let light_pdf = ctx.lights.pdf_value(ray.origin(), ray.direction());
+                    emitted = emitted.scalar_mul(power_heuristic(bsdf_pdf, light_pdf));
+                }
+                *infinite = *infinite + (beta * emitted);
+            }
+            break
+        };
+        let srec = rec.mat.scatter(&ray, &rec);
+        let (p, normal) = (rec.p, rec.normal);
+        let prev = path.len() - 1;
+        let mut vertex = Vertex {
+            kind: VertexKind::Surface { rec, r_in: Ray::with_time(ray.origin(), ray.direction(), ray.time()), srec },
+            p,
+            normal: Some(normal),
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        };
+        vertex.pdf_fwd = path[prev].convert_density(pdf, &vertex);
+        path.push(vertex);
+        if path.len() >= max_vertices {
+            break
+        }
+
+        let back = path[prev].p - p;
+        let vertex = &mut path[prev + 1];
+        let VertexKind::Surface { rec, r_in, srec: Some(srec) } = &vertex.kind else {
+            break
+        };
+        let (scattered, pdf_rev) = match &srec.direction {
+            ScatterDirection::Specular(scattered) => {
+                beta = beta * srec.attenuation;
+                pdf = 0.0;
+                bsdf_pdf = None;
+                vertex.delta = true;
+                (Ray::with_time(scattered.origin(), scattered.direction(), scattered.time()), 0.0)
+            }
+            ScatterDirection::Sampled(bsdf) => {
+                if let Some(infinite) = infinite.as_deref_mut() {
+                    *infinite = *infinite + (beta * sample_infinite_lights(r_in, rec, srec, ctx));
+                }
+                let scattered = Ray::with_time(p, bsdf.generate(), r_in.time());
+                pdf = bsdf.value(scattered.direction());
+                if pdf <= 0.0 {
+                    break
+                }
+                beta = (beta * rec.mat.eval(r_in, rec, srec, &scattered)).scalar_div(pdf);
+                bsdf_pdf = Some(pdf);
+                let pdf_rev = scattering_pdf(rec, p + scattered.direction(), back, r_in.time());
+                (scattered, pdf_rev)
+            }
+        };
+        let pdf_rev = path[prev + 1].convert_density(pdf_rev, &path[prev]);
+        path[prev].pdf_rev = pdf_rev;
+        if beta.near_zero() {
+            break
+        }
+        ray = scattered;
+    }
+}
+
+// Solid-angle density of the material at `rec` sampling `direction` for
+// light arriving from `from`.
+fn scattering_pdf(rec: &HitRecord, from: Point3, direction: Vec3, time: f64) -> f64 {
+    let r_in = Ray::with_time(from, rec.p - from, time);
+    let mut rec = rec.clone();
+    let outward_normal = if rec.front_face { rec.normal } else { rec.normal.scalar_mul(-1.0) };
+    rec.set_face_normal(&r_in, outward_normal);
+    rec.mat.sampling_pdf(&r_in, &rec, &Ray::with_time(rec.p, direction, time))
+}
+
+// Light paths leave surfaces cosine-distributed, on either side.
+fn emission_direction_pdf(normal: Vec3, direction: Vec3) -> f64 {
+    Vec3::dot_product(normal, direction.unit_vector()).abs() / (2.0 * PI)
+}
+
+// `color` for a path through `a` and `b`, which still lacks the squared
+// distance between them, or black if they can't see each other.
+fn joined(color: Vec3, a: Point3, b: Point3, time: f64, ctx: &RenderContext) -> Vec3 {
+    if color.near_zero() {
+        return black()
+    }
+    let offset = b - a;
+    let distance = offset.length();
+    let shadow_ray = Ray::with_time(a, offset.scalar_div(distance), time);
+    let mut occluder = HitRecord::new();
+    if ctx.world.hit(&shadow_ray, Interval::new(0.001, distance - 0.001), &mut occluder) {
+        return black()
+    }
+    color.scalar_div(distance * distance)
+}
+
+// Next-event estimation for lights at infinity only; a sample that lands on
+// anything in the world is left to the bidirectional strategies.
+fn sample_infinite_lights(r: &Ray, rec: &HitRecord, srec: &ScatterRecord, ctx: &RenderContext) -> Vec3 {
+    let (direction, light_pdf, irradiance) = match ctx.lights.sample(rec.p) {
+        Some(LightSample::Emitter { direction, pdf }) if pdf > 0.0 => (direction, Some(pdf), black()),
+        Some(LightSample::Delta { direction, distance, irradiance }) if distance.is_infinite() => (direction, None, irradiance),
+        _ => return black(),
+    };
+    let shadow_ray = Ray::with_time(rec.p, direction, r.time());
+    let bsdf_cos = rec.mat.eval(r, rec, srec, &shadow_ray);
+    let mut occluder = HitRecord::new();
+    if bsdf_cos.near_zero() || ctx.world.hit(&shadow_ray, Interval::new(0.001, f64::INFINITY), &mut occluder) {
+        return black()
+    }
+    let Some(light_pdf) = light_pdf else {
+        return bsdf_cos * irradiance
+    };
+    let bsdf_pdf = match &srec.direction {
+        ScatterDirection::Sampled(bsdf) => bsdf.value(direction),
+        ScatterDirection::Specular(_) => 0.0,
+    };
+    let weight = power_heuristic(light_pdf, bsdf_pdf);
+    (bsdf_cos * ctx.background.value(&shadow_ray)).scalar_mul(weight / light_pdf)
+}
+
+// The densities of a vertex, as changed for one strategy.
+#[derive(Clone, Copy)]
+struct Densities {
+    fwd: f64,
+    rev: f64,
+    delta: bool,
+}
+
+impl Densities {
+    fn of(v: &Vertex) -> Densities {
+        Densities { fwd: v.pdf_fwd, rev: v.pdf_rev, delta: v.delta }
+    }
+}
+
+// The balance heuristic weight of strategy (s, t) for its path, from the
+// ratios of the densities with which the other strategies would have made
+// it. `sampled` is the light vertex for s = 1 or the lens vertex for t = 1.
+fn mis_weight(light_path: &[Vertex], camera_path: &[Vertex], sampled: Option<&Vertex>, s: usize, t: usize, ctx: &RenderContext) -> f64 {
+    if s + t == 2 {
+        return 1.0
+    }
+    let densities = |path: &[Vertex], n: usize| -> Vec<Densities> {
+        if n == 1 && let Some(sampled) = sampled {
+            vec![Densities::of(sampled)]
+        } else {
+            path[..n].iter().map(Densities::of).collect()
+        }
+    };
+    let mut light = densities(light_path, s);
+    let mut camera = densities(camera_path, t);
+
+    let qs = match s {
+        0 => None,
+        1 => sampled,
+        _ => Some(&light_path[s - 1]),
+    };
+    let pt = if t == 1 { sampled.unwrap() } else { &camera_path[t - 1] };
+    let qs_minus = (s > 1).then(|| &light_path[s - 2]);
+    let pt_minus = (t > 1).then(|| &camera_path[t - 2]);
+
+    // The vertices at the join scatter for this strategy, whatever they did
+    // on their own paths, and get densities from the other side.
+    camera[t - 1].delta = false;
+    camera[t - 1].rev = match qs {
+        Some(qs) => qs.pdf(qs_minus, pt, ctx),
+        None => {
+            // An emitter hit by the camera path; if light paths can't start
+            // on it, no other strategy finds this path.
+            let pdf = ctx.lights.point_pdf(pt_minus.unwrap().p, pt.p);
+            if pdf <= 0.0 {
+                return 1.0
+            }
+            pdf
+        }
+    };
+    if let Some(pt_minus) = pt_minus {
+        camera[t - 2].rev = match qs {
+            Some(qs) => pt.pdf(Some(qs), pt_minus, ctx),
+            None => pt.pdf_light(pt_minus),
+        };
+    }
+    if let Some(qs) = qs {
+        light[s - 1].delta = false;
+        light[s - 1].rev = pt.pdf(pt_minus, qs, ctx);
+    }
+    if let (Some(qs), Some(qs_minus)) = (qs, qs_minus) {
+        light[s - 2].rev = qs.pdf(Some(pt), qs_minus, ctx);
+    }
+
+    // Zero densities belong to specular bounces, which the delta flags
+    // already rule out.
+    let remap = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+    let mut sum = 0.0;
+    let mut ratio = 1.0;
+    for i in (1..t).rev() {
+        ratio *= remap(camera[i].rev) / remap(camera[i].fwd);
+        if !camera[i].delta && !camera[i - 1].delta {
+            sum += ratio;
+        }
+    }
+    ratio = 1.0;
+    for i in (0..s).rev() {
+        ratio *= remap(light[i].rev) / remap(light[i].fwd);
+        let delta_before = match (i, s) {
+            (0, 1) => sampled.unwrap().is_point_light(),
+            (0, _) => light_path[0].is_point_light(),
+            _ => light[i - 1].delta,
+        };
+        if !light[i].delta && !delta_before {
+            sum += ratio;
+        }
+    }
+    1.0 / (1.0 + sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::background::Background;
+    use crate::camera::{Camera, CameraSettings};
+    use crate::hittable_list::HittableList;
+    use crate::image::Image;
+    use crate::integrator::PathTracer;
+    use crate::light::{AreaLight, LightList};
+    use crate::material::Lambertian;
+    use crate::quad::Quad;
+
+    // Runs the bidirectional tracer for its splats only, dropping what the
+    // camera paths gather.
+    struct LightTracingOnly;
+
+    impl Integrator for LightTracingOnly {
+        fn radiance(&self, r: &Ray, ctx: &RenderContext) -> Vec3 {
+            BidirectionalPathTracer.radiance(r, ctx);
+            black()
+        }
+    }
+
+    // A diffuse box open towards the camera, lit by a small quad on the
+    // ceiling that is just out of view.
+    fn cornell_box() -> (HittableList, LightList) {
+        let white = Arc::new(Lambertian::new(Vec3::new(0.73, 0.73, 0.73)));
+        let red = Arc::new(Lambertian::new(Vec3::new(0.65, 0.05, 0.05)));
+        let green = Arc::new(Lambertian::new(Vec3::new(0.12, 0.45, 0.15)));
+        let (x, y, z) = (Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, 2.0));
+        let corner = Point3::new(-1.0, 0.0, -2.0);
+
+        let mut world = HittableList::new();
+        world.add(Arc::new(Quad::new(corner, x, z, white.clone())));
+        world.add(Arc::new(Quad::new(corner + y, x, z, white.clone())));
+        world.add(Arc::new(Quad::new(corner, x, y, white)));
+        world.add(Arc::new(Quad::new(corner, z, y, red)));
+        world.add(Arc::new(Quad::new(corner + x, z, y, green)));
+        let light = Arc::new(AreaLight::rect(Point3::new(-0.3, 1.99, -1.3), Vec3::new(0.6, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.6), Vec3::new(1.0, 1.0, 1.0), 10.0));
+        world.add(light.shape.clone());
+        let mut lights = LightList::new();
+        lights.add(light);
+        (world, lights)
+    }
+
+    fn render(integrator: Box<dyn Integrator>) -> Image {
+        let (world, lights) = cornell_box();
+        let mut camera = Camera::new(CameraSettings {
+            aspect_ratio: 1.0,
+            image_width: 16,
+            samples_per_pixel: 64,
+            max_depth: 8,
+            vfov: 40,
+            lookfrom: Point3::new(0.0, 1.0, 1.5),
+            lookat: Point3::new(0.0, 0.8, -1.0),
+            ..CameraSettings::default()
+        });
+        camera.background = Background::black();
+        camera.seed = Some(3);
+        camera.integrator = integrator;
+        camera.render(&world, &lights)
+    }
+
+    fn mean(image: &Image) -> Vec3 {
+        let sum = image.pixels.iter().fold(black(), |sum, c| sum + *c);
+        sum.scalar_div(image.pixels.len() as f64)
+    }
+
+    #[test]
+    fn converges_to_the_path_traced_image() {
+        let path_traced = mean(&render(Box::new(PathTracer)));
+        let bidirectional = mean(&render(Box::new(BidirectionalPathTracer)));
+        for (a, b) in [(path_traced.x(), bidirectional.x()), (path_traced.y(), bidirectional.y()), (path_traced.z(), bidirectional.z())] {
+            assert!(a > 0.0);
+            assert!((b / a - 1.0).abs() < 0.03, "path traced {:?}, bidirectional {:?}", path_traced, bidirectional);
+        }
+    }
+
+    #[test]
+    fn light_paths_reach_the_camera_through_diffuse_bounces() {
+        let splats = render(Box::new(LightTracingOnly));
+        let lit = splats.pixels.iter().filter(|c| !c.near_zero()).count();
+        assert!(lit > splats.pixels.len() / 2, "{} of {} pixels got light", lit, splats.pixels.len());
+    }
+}
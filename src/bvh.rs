@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+const SAH_BUCKETS: usize = 12;
+const MAX_LEAF_OBJECTS: usize = 2;
+
+pub struct BvhNode{
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: Aabb,
+    // Objects without a finite box, such as planes. They would give every
+    // split containing them an infinite SAH cost, so they're kept out of the
+    // tree and tested alongside it.
+    unbounded: Vec<Arc<dyn Hittable>>,
+}
+
+impl BvhNode{
+    pub fn new(list: HittableList) -> Self {
+        let mut objects = list.objects;
+        BvhNode::from_objects(&mut objects)
+    }
+
+    pub fn from_objects(objects: &mut [Arc<dyn Hittable>]) -> Self {
+        let (mut bounded, unbounded): (Vec<_>, Vec<_>) = objects
+            .iter()
+            .cloned()
+            .partition(|object| object.bounding_box().surface_area().is_finite());
+        let mut node = BvhNode::from_bounded(&mut bounded);
+        node.unbounded = unbounded;
+        node
+    }
+
+    fn from_bounded(objects: &mut [Arc<dyn Hittable>]) -> Self {
+        let bbox = objects
+            .iter()
+            .fold(Aabb::EMPTY, |acc, object| Aabb::enclosing(&acc, &object.bounding_box()));
+
+        match objects.len() {
+            0 => {
+                let empty: Arc<dyn Hittable> = Arc::new(HittableList::new());
+                return Self { left: empty.clone(), right: empty, bbox, unbounded: Vec::new() }
+            }
+            1 => return Self { left: objects[0].clone(), right: objects[0].clone(), bbox, unbounded: Vec::new() },
+            2 => return Self { left: objects[0].clone(), right: objects[1].clone(), bbox, unbounded: Vec::new() },
+            _ => {}
+        }
+
+        let mid = BvhNode::partition(objects);
+        let (left_objects, right_objects) = objects.split_at_mut(mid);
+
+        Self {
+            left: BvhNode::subtree(left_objects),
+            right: BvhNode::subtree(right_objects),
+            bbox,
+            unbounded: Vec::new(),
+        }
+    }
+
+    fn subtree(objects: &mut [Arc<dyn Hittable>]) -> Arc<dyn Hittable> {
+        if objects.len() == 1 {
+            objects[0].clone()
+        } else {
+            Arc::new(BvhNode::from_bounded(objects))
+        }
+    }
+
+    // Splits `objects` in place and returns the index of the first object on
+    // the right. Uses a binned surface area heuristic along the axis with the
+    // widest spread of centroids, falling back to a median split when the
+    // centroids coincide or no bucket boundary beats the median.
+    fn partition(objects: &mut [Arc<dyn Hittable>]) -> usize {
+        let centroid_bounds = objects.iter().fold(Aabb::EMPTY, |acc, object| {
+            let b = object.bounding_box();
+            let c = [b.centroid(0), b.centroid(1), b.centroid(2)];
+            Aabb::enclosing(&acc, &Aabb::new(
+                Interval::new(c[0], c[0]),
+                Interval::new(c[1], c[1]),
+                Interval::new(c[2], c[2]),
+            ))
+        });
+
+        let axis = centroid_bounds.longest_axis();
+        let extent = *centroid_bounds.axis_interval(axis);
+        let mid = objects.len() / 2;
+
+        if extent.size() <= 0.0 || objects.len() <= MAX_LEAF_OBJECTS {
+            BvhNode::median_split(objects, axis);
+            return mid
+        }
+
+        let bucket_of = |object: &Arc<dyn Hittable>| -> usize {
+            let offset = (object.bounding_box().centroid(axis) - extent.min) / extent.size();
+            ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+        };
+
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut bounds = [Aabb::EMPTY; SAH_BUCKETS];
+        for object in objects.iter() {
+            let b = bucket_of(object);
+            counts[b] += 1;
+            bounds[b] = Aabb::enclosing(&bounds[b], &object.bounding_box());
+        }
+
+        let mut best_cost = f64::INFINITY;
+        let mut best_split = 0;
+        for split in 0..SAH_BUCKETS - 1 {
+            let (mut left_box, mut right_box) = (Aabb::EMPTY, Aabb::EMPTY);
+            let (mut left_count, mut right_count) = (0, 0);
+            for b in 0..=split {
+                left_box = Aabb::enclosing(&left_box, &bounds[b]);
+                left_count += counts[b];
+            }
+            for b in split + 1..SAH_BUCKETS {
+                right_box = Aabb::enclosing(&right_box, &bounds[b]);
+                right_count += counts[b];
+            }
+            if left_count == 0 || right_count == 0 {
+                continue
+            }
+            let cost = left_count as f64 * left_box.surface_area()
+                + right_count as f64 * right_box.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = split;
+            }
+        }
+
+        if !best_cost.is_finite() {
+            BvhNode::median_split(objects, axis);
+            return mid
+        }
+
+        let mut boundary = 0;
+        for i in 0..objects.len() {
+            if bucket_of(&objects[i]) <= best_split {
+                objects.swap(i, boundary);
+                boundary += 1;
+            }
+        }
+        boundary
+    }
+
+    fn median_split(objects: &mut [Arc<dyn Hittable>], axis: usize) {
+        let mid = objects.len() / 2;
+        objects.select_nth_unstable_by(mid, |a, b| {
+            a.bounding_box().centroid(axis)
+                .total_cmp(&b.bounding_box().centroid(axis))
+        });
+    }
+}
+
+impl Hittable for BvhNode{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let mut hit_unbounded = false;
+        let mut closest = ray_t.max;
+        for object in &self.unbounded {
+            if object.hit(r, Interval::new(ray_t.min, closest), rec) {
+                hit_unbounded = true;
+                closest = rec.t;
+            }
+        }
+
+        let ray_t = Interval::new(ray_t.min, closest);
+        if !self.bbox.hit(r, ray_t) {
+            return hit_unbounded
+        }
+
+        let hit_left = self.left.hit(r, ray_t, rec);
+        let right_max = if hit_left { rec.t } else { ray_t.max };
+        let hit_right = self.right.hit(r, Interval::new(ray_t.min, right_max), rec);
+
+        hit_unbounded || hit_left || hit_right
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.unbounded
+            .iter()
+            .fold(self.bbox, |acc, object| Aabb::enclosing(&acc, &object.bounding_box()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+    use crate::vec3::{Point3, Vec3};
+    use crate::{random_double_x, seed_thread_rng};
+
+    // Random spheres, each with its own material so a hit can be traced
+    // back to the object, plus an infinite plane beneath them.
+    fn test_scene() -> HittableList {
+        let mut list = HittableList::new();
+        for _ in 0..300 {
+            let center = Vec3::random_vector2(-10.0, 10.0);
+            let mat = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+            list.add(Arc::new(Sphere::new(center, random_double_x(0.1, 1.0), mat)));
+        }
+        let mat = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        list.add(Arc::new(Plane::new(Point3::new(0.0, -8.0, 0.0), Vec3::new(0.0, 1.0, 0.0), mat)));
+        list
+    }
+
+    #[test]
+    fn matches_linear_search() {
+        seed_thread_rng(4);
+        let list = test_scene();
+        let bvh = BvhNode::from_objects(&mut list.objects.clone());
+        assert!(!bvh.unbounded.is_empty());
+
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let r = Ray::new(Vec3::random_vector2(-15.0, 15.0), Vec3::random_unit_vector());
+            let (mut expected, mut actual) = (HitRecord::new(), HitRecord::new());
+            let hit = list.hit(&r, Interval::new(0.001, f64::INFINITY), &mut expected);
+            assert_eq!(bvh.hit(&r, Interval::new(0.001, f64::INFINITY), &mut actual), hit);
+            if hit {
+                hits += 1;
+                assert_eq!(actual.t, expected.t);
+                assert!(std::ptr::addr_eq(Arc::as_ptr(&actual.mat), Arc::as_ptr(&expected.mat)));
+            }
+        }
+        assert!(hits > 1000);
+    }
+}
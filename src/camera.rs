@@ -1,7 +1,7 @@
-use std::{f64::INFINITY};
+use std::sync::Mutex;
+
 use rayon::prelude::*;
-use crate::{color::write_color, degrees_to_radians, hittable::{HitRecord, Hittable}, interval::Interval, random_double, ray::Ray, vec3::Vec3};
-use std::io::{self, Write, BufWriter};
+use crate::{background::Background, degrees_to_radians, hittable::Hittable, integrator::{Integrator, PathTracer, RenderContext}, light::Light, image::Image, random_double, random_double_x, ray::Ray, seed_thread_rng, vec3::{Point3, Vec3}};
 
 pub struct Camera{
     pub aspect_ratio: f64,
@@ -9,11 +9,19 @@ pub struct Camera{
     pub samples_per_pixel: i64,
     pub max_depth: i64,
     pub vfov: i64,
-    pub lookform: Vec3,
+    pub lookfrom: Vec3,
     pub lookat: Vec3,
     pub vup: Vec3,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    pub seed: Option<u64>,
+    pub background: Background,
+    // Each sample ray gets a random time in [shutter_open, shutter_close];
+    // moving objects blur across that interval.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    // The light transport algorithm; a `PathTracer` unless replaced.
+    pub integrator: Box<dyn Integrator>,
     image_height: i64,
     center: Vec3,
     pixel100_loc: Vec3,
@@ -27,19 +35,57 @@ pub struct Camera{
     defocus_disk_v: Vec3,
 }
 
+// The user-facing camera parameters, so `Camera::new` doesn't take a long
+// positional argument list.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraSettings {
+    pub aspect_ratio: f64,
+    pub image_width: i64,
+    pub samples_per_pixel: i64,
+    pub max_depth: i64,
+    pub vfov: i64,
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub defocus_angle: f64,
+    pub focus_dist: f64,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 400,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            vfov: 90,
+            lookfrom: Point3::new(0.0, 0.0, 0.0),
+            lookat: Point3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+        }
+    }
+}
+
 impl Camera{
-    pub fn new(aspect_ratio: f64, image_width: i64, samples_per_pixel :i64, max_depth: i64, vfov: i64, lookform: Vec3, lookat: Vec3, vup: Vec3,defocus_angle: f64, focus_dist:f64) -> Self {
+    pub fn new(settings: CameraSettings) -> Self {
         Self {
-            aspect_ratio,
-            image_width,
-            samples_per_pixel,
-            max_depth,
-            vfov,
-            lookform,
-            lookat,
-            vup,
-            defocus_angle,
-            focus_dist,
+            aspect_ratio: settings.aspect_ratio,
+            image_width: settings.image_width,
+            samples_per_pixel: settings.samples_per_pixel,
+            max_depth: settings.max_depth,
+            vfov: settings.vfov,
+            lookfrom: settings.lookfrom,
+            lookat: settings.lookat,
+            vup: settings.vup,
+            defocus_angle: settings.defocus_angle,
+            focus_dist: settings.focus_dist,
+            seed: None,
+            background: Background::default(),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            integrator: Box::new(PathTracer),
             image_height: 0,
             center: Vec3::new(0.0, 0.0, 0.0),
             pixel100_loc: Vec3::new(0.0, 0.0, 0.0),
@@ -55,38 +101,37 @@ impl Camera{
     }
 
     fn initialize(&mut self){
-        self.image_height = (self.image_width as f64 /self.aspect_ratio) as i64;
-        self.image_height = if self.image_height < 1 { 1 } else { self.image_height };
+        self.image_height = self.image_height();
 
         self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
         
-        self.center = self.lookform;
+        self.center = self.lookfrom;
 
         let theta = degrees_to_radians(self.vfov as f64);
         let h = (theta/2.0).tan();
         let viewport_height = 2.0 * h * self.focus_dist;
         let viewport_width : f64 = viewport_height * (self.image_width as f64 / self.image_height as f64);
 
-        self.w = Vec3::sub(self.lookform, self.lookat).unit_vector();
+        self.w = (self.lookfrom - self.lookat).unit_vector();
         self.u = Vec3::cross_product(self.vup, self.w).unit_vector();
         self.v = Vec3::cross_product(self.w, self.u);
 
         let viewport_u = self.u.scalar_mul(viewport_width);
-        let viewport_v = self.v.scalar_mul(-1.0 * viewport_height);
+        let viewport_v = self.v.scalar_mul(-viewport_height);
 
         self.pixel_delta_u = viewport_u.scalar_div(self.image_width as f64);
         self.pixel_delta_v = viewport_v.scalar_div(self.image_height as f64);
         
         let t = viewport_u.scalar_div(2.0);
         let o = viewport_v.scalar_div(2.0);
-        let p = Vec3::add(t,o);
+        let p = t + o;
         let j = self.w.scalar_mul(self.focus_dist);
-        let q = Vec3::add(p,j);
-        let viewport_upper_left = Vec3::sub(self.center,q);
+        let q = p + j;
+        let viewport_upper_left = self.center - q;
 
-        let sum1 = Vec3::add(self.pixel_delta_u,self.pixel_delta_v);
+        let sum1 = self.pixel_delta_u + self.pixel_delta_v;
         let q = sum1.scalar_mul(0.5);
-        self.pixel100_loc = Vec3::add(q, viewport_upper_left);
+        self.pixel100_loc = q + viewport_upper_left;
         
         let defocus_radius = self.focus_dist * (degrees_to_radians(self.defocus_angle/2.0)).tan();
         self.defocus_disk_u = self.u.scalar_mul(defocus_radius);
@@ -94,75 +139,153 @@ impl Camera{
 
     }
 
-    fn ray_color<T: Hittable>(r: &Ray,depth: i64, world: &T) -> Vec3{
-        if depth <= 0 {
-            return Vec3::new(0.0,0.0,0.0);
-        }
-        let mut rec = HitRecord::new();
-        if world.hit(r, Interval::new(0.001, INFINITY), &mut rec){
-            let mut scattered = Ray::new(Vec3::new(0.0,0.0,0.0), Vec3::new(0.0,0.0,0.0));
-            let mut attenuation = Vec3::new(0.0,0.0,0.0);
-
-            if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered){
-                return Vec3::mul(attenuation, Camera::ray_color(&scattered, depth -1 , world))
-            }
-            return Vec3::new(0.0, 0.0, 0.0)
-        }
-
-        let unit_direction = (r.direction()).unit_vector();
-        let a = (unit_direction.y() + 1.0 ) * (0.5);
-        return Vec3::add(Vec3::new(1.0,1.0,1.0).scalar_mul(1.0-a), Vec3::new(0.5,0.7,1.0).scalar_mul(a))
-
-    }
-    
     fn sample_square() -> Vec3{
         Vec3::new(random_double()-0.5,random_double()-0.5,0.0)
     }
 
     fn defocus_disk_sample(&self) -> Vec3{
         let p = Vec3::random_in_unit_disk();
-        return Vec3::add(self.center, Vec3::add(self.defocus_disk_u.scalar_mul(p.x()),self.defocus_disk_v.scalar_mul(p.y())));
+        self.center + (self.defocus_disk_u.scalar_mul(p.x()) + self.defocus_disk_v.scalar_mul(p.y()))
     }
 
     fn get_ray(&self,i: i64, j: i64) -> Ray {
         let offset = Camera::sample_square();
         let g = self.pixel_delta_u.scalar_mul(i as f64 + offset.x());
         let h = self.pixel_delta_v.scalar_mul(j as f64 + offset.y());
-        let k = Vec3::add(g,h);
-        let pixel_sample = Vec3::add(self.pixel100_loc,k);
+        let k = g + h;
+        let pixel_sample = self.pixel100_loc + k;
         let ray_origin = if self.defocus_angle <= 0.0 { self.center } else{ self.defocus_disk_sample() };
-        let ray_direction = Vec3::sub(pixel_sample, ray_origin);
-        return Ray::new(ray_origin, ray_direction)
+        let ray_direction = pixel_sample - ray_origin;
+        let ray_time = if self.shutter_close > self.shutter_open {
+            random_double_x(self.shutter_open, self.shutter_close)
+        } else {
+            self.shutter_open
+        };
+        Ray::with_time(ray_origin, ray_direction, ray_time)
     }
 
-    pub fn render<T: Hittable + Sync>(&mut self, world: &T){
+    // Picks a point on the lens that `p` could be seen from, for joining
+    // light paths to the camera. None if `p` is outside the view.
+    pub fn sample_lens(&self, p: Point3) -> Option<LensSample> {
+        let origin = if self.defocus_angle <= 0.0 { self.center } else { self.defocus_disk_sample() };
+        let direction = p - origin;
+        let depth = -Vec3::dot_product(direction, self.w);
+        if depth <= 0.0 {
+            return None
+        }
+
+        // Where the ray crosses the focus plane, in pixels; pixel centres are
+        // at whole numbers.
+        let on_plane = origin + direction.scalar_mul(self.focus_dist / depth) - self.pixel100_loc;
+        let x = Vec3::dot_product(on_plane, self.pixel_delta_u) / self.pixel_delta_u.length_squared() + 0.5;
+        let y = Vec3::dot_product(on_plane, self.pixel_delta_v) / self.pixel_delta_v.length_squared() + 0.5;
+        if x < 0.0 || y < 0.0 || x >= self.image_width as f64 || y >= self.image_height as f64 {
+            return None
+        }
+
+        let importance = self.direction_pdf(direction);
+        Some(LensSample { origin, pixel: (x as usize, y as usize), importance })
+    }
+
+    // Solid-angle density of a sample ray leaving the lens in `direction`,
+    // counting the whole image: film points are uniform over the focus
+    // plane, whose area grows as 1/cos³ away from the axis.
+    pub fn direction_pdf(&self, direction: Vec3) -> f64 {
+        let cos_theta = -Vec3::dot_product(direction.unit_vector(), self.w);
+        if cos_theta <= 0.0 {
+            return 0.0
+        }
+        let film_area = self.pixel_delta_u.length() * self.image_width as f64 * self.pixel_delta_v.length() * self.image_height as f64;
+        self.focus_dist * self.focus_dist / (film_area * cos_theta * cos_theta * cos_theta)
+    }
+
+    pub fn image_height(&self) -> i64 {
+        // The small bias keeps an aspect ratio of exactly width/height from
+        // truncating to one pixel short.
+        let height = (self.image_width as f64 / self.aspect_ratio + 1e-9) as i64;
+        if height < 1 { 1 } else { height }
+    }
+
+    // Renders the scene into a linear-radiance image. Nothing is written
+    // anywhere; pass the result to one of the encoders in `output`.
+    // `lights` chooses the emitters to sample directly: a `LightList`, or a
+    // `LightBvh` or `PowerLightSampler` built from one for many lights. Area
+    // lights must also be part of `world`. `integrator` turns each sample
+    // ray into radiance.
+    pub fn render<T: Hittable>(&mut self, world: &T, lights: &dyn Light) -> Image {
         self.initialize();
-        let stdout = io::stdout();
-        let mut out = BufWriter::new(stdout.lock());
+        let splats = Splats::new(self.image_width as usize, self.image_height as usize);
+        let ctx = RenderContext {
+            world,
+            lights,
+            background: &self.background,
+            max_depth: self.max_depth,
+            camera: self,
+            splats: &splats,
+        };
 
-        writeln!(out, "P3\n{} {}\n255", self.image_width, self.image_height).unwrap();
-        // println!("P3\n{} {} \n255\n",self.image_width,self.image_height);
-        let image_pixels: Vec<Vec<Vec3>> = (0..self.image_height)
+        let rows: Vec<Vec<Vec3>> = (0..self.image_height)
             .into_par_iter()
             .map(|j| {
                 (0..self.image_width)
                     .map(|i| {
+                        // Seeding per pixel keeps seeded renders identical
+                        // regardless of how rayon schedules the rows.
+                        if let Some(seed) = self.seed {
+                            seed_thread_rng(pixel_seed(seed, i, j));
+                        }
                         let mut pixel_color = Vec3::new(0.0, 0.0, 0.0);
                         for _ in 0..self.samples_per_pixel {
                             let r = self.get_ray(i, j);
-                            pixel_color = Vec3::add(pixel_color, Camera::ray_color(&r, self.max_depth, world));
+                            pixel_color = pixel_color + self.integrator.radiance(&r, &ctx);
                         }
                         pixel_color.scalar_mul(self.pixel_samples_scale)
                     })
                     .collect() 
             })
-            .collect(); 
-        
-        for row in image_pixels {
-            for pixel_color in row {
-                write_color(&mut out, pixel_color);
-            }
-        }
-        out.flush().unwrap();
+            .collect();
+
+        // Splats are sums over every sample of the image, so they are scaled
+        // like one pixel's samples.
+        let pixels = rows.concat().into_iter()
+            .zip(splats.pixels)
+            .map(|(color, splat)| color + splat.into_inner().unwrap().scalar_mul(self.pixel_samples_scale))
+            .collect();
+        Image::from_pixels(self.image_width as usize, self.image_height as usize, pixels)
     }
+}
+
+pub struct LensSample {
+    pub origin: Point3,
+    // The pixel (x, y) the ray from `origin` towards the point passes through.
+    pub pixel: (usize, usize),
+    // The camera's importance for that ray. It is also `direction_pdf`;
+    // divided by the squared distance to the point, it weighs light
+    // arriving there into the pixel.
+    pub importance: f64,
+}
+
+// Radiance added to arbitrary pixels during a render, such as light paths
+// that reach the lens. Shared by all threads.
+pub struct Splats {
+    width: usize,
+    pixels: Vec<Mutex<Vec3>>,
+}
+
+impl Splats {
+    pub fn new(width: usize, height: usize) -> Splats {
+        Splats { width, pixels: (0..width * height).map(|_| Mutex::new(Vec3::new(0.0, 0.0, 0.0))).collect() }
+    }
+
+    pub fn add(&self, (x, y): (usize, usize), color: Vec3) {
+        let mut pixel = self.pixels[y * self.width + x].lock().unwrap();
+        *pixel = *pixel + color;
+    }
+}
+
+fn pixel_seed(seed: u64, i: i64, j: i64) -> u64 {
+    let mut h = seed ^ ((j as u64) << 32 | i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^ (h >> 33)
 }
\ No newline at end of file
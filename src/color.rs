@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::io::{self, Write};
 use crate::{interval::Interval, vec3::Vec3};
 
 fn linear_to_gamma(linear_component: f64) -> f64{
@@ -8,16 +8,29 @@ fn linear_to_gamma(linear_component: f64) -> f64{
     0.0
 }
 
-pub fn write_color<W: Write>(out: &mut W, pixel_color: Vec3) {
-    let r = linear_to_gamma(pixel_color.x());
-    let g = linear_to_gamma(pixel_color.y());
-    let b = linear_to_gamma(pixel_color.z());
+// Inverse of the gamma applied on output, for decoding 8-bit images.
+pub fn gamma_to_linear(gamma_component: f64) -> f64{
+    if gamma_component > 0.0 {
+        return gamma_component * gamma_component
+    }
+    0.0
+}
+
+// Gamma-corrects a linear colour and scales each channel to `0..levels`.
+fn quantize(pixel_color: Vec3, levels: f64) -> [u32; 3] {
+    let intensity = Interval::new(0.000, 0.999_99);
+    pixel_color.e.map(|c| (levels * Interval::clamp(&intensity, linear_to_gamma(c))) as u32)
+}
 
-    let intensity = Interval::new(0.000, 0.999);
+pub fn color_to_u8(pixel_color: Vec3) -> [u8; 3] {
+    quantize(pixel_color, 256.0).map(|c| c as u8)
+}
 
-    let rbyte = (256.0 * Interval::clamp(&intensity, r)) as i64;
-    let gbyte = (256.0 * Interval::clamp(&intensity, g)) as i64;
-    let bbyte = (256.0 * Interval::clamp(&intensity, b)) as i64;
+pub fn color_to_u16(pixel_color: Vec3) -> [u16; 3] {
+    quantize(pixel_color, 65536.0).map(|c| c as u16)
+}
 
-    writeln!(out, "{} {} {}", rbyte, gbyte, bbyte).unwrap();
-}
\ No newline at end of file
+pub fn write_color<W: Write>(out: &mut W, pixel_color: Vec3) -> io::Result<()> {
+    let [rbyte, gbyte, bbyte] = color_to_u8(pixel_color);
+    writeln!(out, "{} {} {}", rbyte, gbyte, bbyte)
+}
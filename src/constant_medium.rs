@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::{Isotropic, Material};
+use crate::random_double;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+
+// A volume of constant density filling a boundary shape. Rays passing
+// through it scatter at an exponentially distributed distance; the boundary
+// must be closed and convex for the entry/exit test to be right.
+pub struct ConstantMedium{
+    pub boundary : Arc<dyn Hittable>,
+    pub neg_inv_density : f64,
+    pub phase_function : Arc<dyn Material>,
+}
+
+impl ConstantMedium{
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, albedo: Vec3) -> ConstantMedium {
+        ConstantMedium::with_phase_function(boundary, density, Arc::new(Isotropic::new(albedo)))
+    }
+
+    pub fn from_texture(boundary: Arc<dyn Hittable>, density: f64, tex: Arc<dyn Texture>) -> ConstantMedium {
+        ConstantMedium::with_phase_function(boundary, density, Arc::new(Isotropic::from_texture(tex)))
+    }
+
+    pub fn with_phase_function(boundary: Arc<dyn Hittable>, density: f64, phase_function: Arc<dyn Material>) -> ConstantMedium {
+        ConstantMedium { boundary, neg_inv_density: -1.0 / density, phase_function }
+    }
+}
+
+impl Hittable for ConstantMedium{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Find where the ray's line enters and leaves the boundary. Searching
+        // from -inf means rays that start inside the volume still see the
+        // entry point behind them.
+        let mut rec1 = HitRecord::new();
+        let mut rec2 = HitRecord::new();
+
+        if !self.boundary.hit(r, Interval::UNIVERSE, &mut rec1) {
+            return false
+        }
+        if !self.boundary.hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY), &mut rec2) {
+            return false
+        }
+
+        rec1.t = rec1.t.max(ray_t.min);
+        rec2.t = rec2.t.min(ray_t.max);
+        if rec1.t >= rec2.t {
+            return false
+        }
+        rec1.t = rec1.t.max(0.0);
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
+        let hit_distance = self.neg_inv_density * random_double().ln();
+
+        if hit_distance > distance_inside_boundary {
+            return false
+        }
+
+        rec.t = rec1.t + hit_distance / ray_length;
+        rec.p = r.ray_at(rec.t);
+
+        // Arbitrary; the phase function ignores them.
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.front_face = true;
+        rec.u = 0.0;
+        rec.v = 0.0;
+        rec.mat = self.phase_function.clone();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
@@ -0,0 +1,243 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::path::Path;
+
+use ::image::error::{ImageError, ParameterError, ParameterErrorKind};
+
+use crate::light::{Light, LightSample};
+use crate::random_double;
+use crate::vec3::{Point3, Vec3};
+
+// Equirectangular (latitude/longitude) radiance map surrounding the scene,
+// laid out like sphere UVs: the top row looks straight up and u runs around
+// the Y axis. Directions are importance sampled in proportion to luminance
+// through a marginal CDF over rows and a conditional CDF within each row.
+pub struct EnvironmentMap{
+    pub width: usize,
+    pub height: usize,
+    // Rotation about the Y axis in degrees.
+    pub rotation: f64,
+    pub intensity: f64,
+    texels: Vec<Vec3>,
+    sin_rotation: f64,
+    cos_rotation: f64,
+    marginal_cdf: Vec<f64>,
+    // `height` rows of `width + 1` entries.
+    conditional_cdf: Vec<f64>,
+    // Sum of the sampling weights, for turning them into densities.
+    total_weight: f64,
+}
+
+impl EnvironmentMap{
+    // Fails if the map is empty or `texels` doesn't match its size.
+    pub fn new(width: usize, height: usize, texels: Vec<Vec3>, rotation: f64, intensity: f64) -> Result<EnvironmentMap, ImageError> {
+        if width == 0 || height == 0 {
+            return Err(parameter_error("environment map is empty".to_string()))
+        }
+        if texels.len() != width * height {
+            return Err(parameter_error(format!("texel count does not match {}x{}", width, height)))
+        }
+
+        // Rows near the poles cover less solid angle, hence the sin(theta).
+        let weights: Vec<f64> = (0..height)
+            .flat_map(|j| {
+                let sin_theta = (PI * (j as f64 + 0.5) / height as f64).sin();
+                texels[j * width..(j + 1) * width].iter().map(move |c| luminance(*c).max(0.0) * sin_theta)
+            })
+            .collect();
+        // An all-black map still needs a valid distribution.
+        let weights = if weights.iter().sum::<f64>() > 0.0 {
+            weights
+        } else {
+            (0..width * height).map(|k| (PI * ((k / width) as f64 + 0.5) / height as f64).sin()).collect()
+        };
+
+        let mut conditional_cdf = Vec::with_capacity(height * (width + 1));
+        let mut row_sums = Vec::with_capacity(height);
+        for row in weights.chunks(width) {
+            let mut sum = 0.0;
+            conditional_cdf.push(0.0);
+            for w in row {
+                sum += w;
+                conditional_cdf.push(sum);
+            }
+            row_sums.push(sum);
+        }
+        let mut marginal_cdf = Vec::with_capacity(height + 1);
+        let mut total_weight = 0.0;
+        marginal_cdf.push(0.0);
+        for sum in &row_sums {
+            total_weight += sum;
+            marginal_cdf.push(total_weight);
+        }
+
+        let (sin_rotation, cos_rotation) = crate::degrees_to_radians(rotation).sin_cos();
+        Ok(EnvironmentMap {
+            width,
+            height,
+            rotation,
+            intensity,
+            texels,
+            sin_rotation,
+            cos_rotation,
+            marginal_cdf,
+            conditional_cdf,
+            total_weight,
+        })
+    }
+
+    // Loads a Radiance HDR or OpenEXR file. Values are used as stored
+    // (linear radiance). An image with no pixels is an error.
+    pub fn open(path: &Path, rotation: f64, intensity: f64) -> Result<EnvironmentMap, ImageError> {
+        let rgb = ::image::open(path)?.into_rgb32f();
+        let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+        let texels = rgb.pixels().map(|p| Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64)).collect();
+        EnvironmentMap::new(width, height, texels, rotation, intensity)
+    }
+
+    pub fn value(&self, direction: Vec3) -> Vec3 {
+        let (i, j) = self.texel_index(self.to_map(direction.unit_vector()));
+        self.texels[j * self.width + i].scalar_mul(self.intensity)
+    }
+
+    // Solid-angle density of `sample` returning `direction`.
+    pub fn pdf(&self, direction: Vec3) -> f64 {
+        let d = self.to_map(direction.unit_vector());
+        let sin_theta = (1.0 - d.y() * d.y()).max(0.0).sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0
+        }
+        let (i, j) = self.texel_index(d);
+        let row = &self.conditional_cdf[j * (self.width + 1)..];
+        let weight = row[i + 1] - row[i];
+
+        // Density over the unit (u, v) square, then over the sphere.
+        let pdf_uv = weight / self.total_weight * (self.width * self.height) as f64;
+        pdf_uv / (2.0 * PI * PI * sin_theta)
+    }
+
+    pub fn sample(&self) -> Vec3 {
+        let j = sample_cdf(&self.marginal_cdf, random_double() * self.total_weight);
+        let row = &self.conditional_cdf[j * (self.width + 1)..(j + 1) * (self.width + 1)];
+        let i = sample_cdf(row, random_double() * row[self.width]);
+
+        let u = (i as f64 + random_double()) / self.width as f64;
+        let v = 1.0 - (j as f64 + random_double()) / self.height as f64;
+        let theta = v * PI;
+        let phi = u * 2.0 * PI;
+        let d = Vec3::new(-phi.cos() * theta.sin(), -theta.cos(), phi.sin() * theta.sin());
+        self.to_world(d)
+    }
+
+    fn texel_index(&self, d: Vec3) -> (usize, usize) {
+        let theta = (-d.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-d.z()).atan2(d.x()) + PI;
+        let u = phi / (2.0 * PI);
+        let v = theta / PI;
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = (((1.0 - v) * self.height as f64) as usize).min(self.height - 1);
+        (i, j)
+    }
+
+    fn to_map(&self, d: Vec3) -> Vec3 {
+        Vec3::new(self.cos_rotation * d.x() - self.sin_rotation * d.z(), d.y(), self.sin_rotation * d.x() + self.cos_rotation * d.z())
+    }
+
+    fn to_world(&self, d: Vec3) -> Vec3 {
+        Vec3::new(self.cos_rotation * d.x() + self.sin_rotation * d.z(), d.y(), -self.sin_rotation * d.x() + self.cos_rotation * d.z())
+    }
+}
+
+impl fmt::Debug for EnvironmentMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnvironmentMap")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("rotation", &self.rotation)
+            .field("intensity", &self.intensity)
+            .finish_non_exhaustive()
+    }
+}
+
+// Pair the light with `Background::Environment` so that shadow rays
+// escaping towards the sampled direction see the same radiance.
+impl Light for EnvironmentMap{
+    fn sample(&self, _origin: Point3) -> Option<LightSample> {
+        let direction = self.sample();
+        let pdf = self.pdf(direction);
+        if pdf <= 0.0 {
+            return None
+        }
+        Some(LightSample::Emitter { direction, pdf })
+    }
+
+    fn pdf_value(&self, _origin: Point3, direction: Vec3) -> f64 {
+        self.pdf(direction)
+    }
+}
+
+fn parameter_error(message: String) -> ImageError {
+    ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(message)))
+}
+
+fn luminance(c: Vec3) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+// Index k with cdf[k] <= x < cdf[k + 1], skipping zero-width entries.
+fn sample_cdf(cdf: &[f64], x: f64) -> usize {
+    let k = cdf.partition_point(|&c| c <= x);
+    k.clamp(1, cdf.len() - 1) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seed_thread_rng;
+
+    // A small map with a bright patch, so the distribution is far from
+    // uniform.
+    fn test_map() -> EnvironmentMap {
+        let (width, height) = (16, 8);
+        let texels = (0..width * height)
+            .map(|k| {
+                let (i, j) = (k % width, k / width);
+                if (3..6).contains(&i) && (2..4).contains(&j) { Vec3::new(50.0, 40.0, 30.0) } else { Vec3::new(0.2, 0.3, 0.5) }
+            })
+            .collect();
+        EnvironmentMap::new(width, height, texels, 30.0, 1.0).unwrap()
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        seed_thread_rng(1);
+        let map = test_map();
+        let n = 200_000;
+        let mean = (0..n).map(|_| map.pdf(Vec3::random_unit_vector())).sum::<f64>() / n as f64;
+        assert!((mean * 4.0 * PI - 1.0).abs() < 0.03, "integral {}", mean * 4.0 * PI);
+    }
+
+    #[test]
+    fn samples_follow_the_pdf() {
+        seed_thread_rng(2);
+        let map = test_map();
+        let n = 200_000;
+        // E[1/pdf] over samples is the solid angle the distribution covers.
+        let mut sphere = 0.0;
+        for _ in 0..n {
+            let pdf = map.pdf(map.sample());
+            assert!(pdf > 0.0);
+            sphere += 1.0 / pdf;
+        }
+        let sphere = sphere / n as f64;
+        assert!((sphere / (4.0 * PI) - 1.0).abs() < 0.03, "solid angle {}", sphere);
+    }
+
+    #[test]
+    fn empty_map_is_rejected() {
+        let err = EnvironmentMap::new(0, 4, Vec::new(), 0.0, 1.0).unwrap_err();
+        assert!(err.to_string().contains("environment map is empty"), "{}", err);
+        let err = EnvironmentMap::new(2, 2, vec![Vec3::new(1.0, 1.0, 1.0); 3], 0.0, 1.0).unwrap_err();
+        assert!(err.to_string().contains("texel count does not match 2x2"), "{}", err);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::aabb::Aabb;
 use crate::ray::*;
 use crate::vec3::{Point3,Vec3};
 use crate::interval::Interval;
@@ -10,6 +11,8 @@ pub struct HitRecord{
     pub normal : Vec3,
     pub mat: Arc<dyn Material>,
     pub t : f64,
+    pub u : f64,
+    pub v : f64,
     pub front_face : bool,
 }
 
@@ -20,6 +23,8 @@ impl HitRecord {
             normal: Vec3::new(0.0, 0.0, 0.0),
             mat : Arc::new(EmptyMaterial),
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
             front_face: false,
         }
     }
@@ -41,4 +46,35 @@ impl Default for HitRecord {
 
 pub trait Hittable : Send + Sync{
     fn hit(&self,r:&Ray, ray_t : Interval ,rec:&mut HitRecord) -> bool;
+
+    fn bounding_box(&self) -> Aabb;
+
+    // Solid-angle density of `random` picking `direction` from `origin`.
+    // Objects that can't be sampled as lights keep the zero default.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    // A direction from `origin` towards a random point on the object.
+    fn random(&self, _origin: Point3) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // A point picked uniformly over the surface, with the outward normal
+    // and front_face set, and its density 1/area. Light paths start here;
+    // objects that can't be lights keep the None default.
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        None
+    }
+}
+
+// Converts the area density 1/area of a point sampled on a flat surface hit
+// at `t` along `direction` into a solid-angle density at the ray origin.
+pub(crate) fn area_pdf_to_solid_angle(direction: Vec3, t: f64, normal: Vec3, area: f64) -> f64 {
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = (Vec3::dot_product(direction, normal) / direction.length()).abs();
+    if cosine < 1e-8 {
+        return 0.0
+    }
+    distance_squared / (cosine * area)
 }
\ No newline at end of file
@@ -1,29 +1,35 @@
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::ray::Ray;
 use std::sync::Arc;
 use std::vec::Vec;
 use crate::interval::Interval;
+use crate::random_double;
+use crate::vec3::{Point3, Vec3};
 
 pub struct HittableList {
     pub objects: Vec<Arc<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl HittableList {
     pub fn new() -> Self {
-        Self { objects: Vec::new() }
+        Self { objects: Vec::new(), bbox: Aabb::EMPTY }
     }
 
-    pub fn _new_with(object: Arc<dyn Hittable>) -> Self {
+    pub fn new_with(object: Arc<dyn Hittable>) -> Self {
         let mut list = Self::new();
         list.add(object);
         list
     }
 
-    pub fn _clear(&mut self) {
+    pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = Aabb::EMPTY;
     }
 
     pub fn add(&mut self, object: Arc<dyn Hittable>) {
+        self.bbox = Aabb::enclosing(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
 }
@@ -44,6 +50,28 @@ impl Hittable for HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // Picks one object uniformly, so the density is the average of theirs.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0
+        }
+        let sum: f64 = self.objects.iter().map(|object| object.pdf_value(origin, direction)).sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let size = self.objects.len();
+        if size == 0 {
+            return Vec3::new(1.0, 0.0, 0.0)
+        }
+        let index = ((random_double() * size as f64) as usize).min(size - 1);
+        self.objects[index].random(origin)
+    }
 }
 
 impl Default for HittableList {
@@ -0,0 +1,37 @@
+use crate::vec3::Vec3;
+
+// A rendered frame: linear RGB radiance per pixel, stored row by row from
+// the top-left corner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image{
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vec3>,
+}
+
+impl Image{
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![Vec3::new(0.0, 0.0, 0.0); width * height],
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Vec3>) -> Image {
+        assert_eq!(pixels.len(), width * height, "pixel count does not match {}x{}", width, height);
+        Image { width, height, pixels }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Vec3) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn rows(&self) -> std::slice::Chunks<'_, Vec3> {
+        self.pixels.chunks(self.width.max(1))
+    }
+}
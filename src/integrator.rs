@@ -0,0 +1,350 @@
+use crate::background::Background;
+use crate::camera::{Camera, Splats};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::light::{Light, LightSample};
+use crate::material::{ScatterDirection, ScatterRecord};
+use crate::onb::Onb;
+use crate::pdf::{random_cosine_direction, Pdf};
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// The scene as an integrator sees it while rendering.
+pub struct RenderContext<'a> {
+    pub world: &'a dyn Hittable,
+    pub lights: &'a dyn Light,
+    pub background: &'a Background,
+    pub max_depth: i64,
+    pub camera: &'a Camera,
+    // For light that reaches pixels other than the one being sampled.
+    pub splats: &'a Splats,
+}
+
+// A light transport algorithm: turns a camera ray into the radiance arriving
+// along it. `Camera::render` calls it for every sample.
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, r: &Ray, ctx: &RenderContext) -> Vec3;
+}
+
+// Path tracing with material sampling only. Emitters are found only when a
+// bounce happens to hit them; the lights are ignored.
+pub struct NaivePathTracer;
+
+impl NaivePathTracer {
+    fn ray_color(&self, r: &Ray, depth: i64, ctx: &RenderContext) -> Vec3 {
+        if depth <= 0 {
+            return black()
+        }
+        let Some(rec) = hit(ctx.world, r) else {
+            return ctx.background.value(r)
+        };
+        let color_from_emission = rec.mat.emitted(r, &rec);
+        let Some(srec) = rec.mat.scatter(r, &rec) else {
+            return color_from_emission
+        };
+
+        let color_from_scatter = match &srec.direction {
+            ScatterDirection::Specular(scattered) => {
+                srec.attenuation * self.ray_color(scattered, depth - 1, ctx)
+            }
+            ScatterDirection::Sampled(pdf) => {
+                let scattered = Ray::with_time(rec.p, pdf.generate(), r.time());
+                let pdf_value = pdf.value(scattered.direction());
+                if pdf_value <= 0.0 {
+                    return color_from_emission
+                }
+                let bsdf_cos = rec.mat.eval(r, &rec, &srec, &scattered);
+                (bsdf_cos * self.ray_color(&scattered, depth - 1, ctx)).scalar_div(pdf_value)
+            }
+        };
+        color_from_emission + color_from_scatter
+    }
+}
+
+impl Integrator for NaivePathTracer {
+    fn radiance(&self, r: &Ray, ctx: &RenderContext) -> Vec3 {
+        self.ray_color(r, ctx.max_depth, ctx)
+    }
+}
+
+// Path tracing with next-event estimation: every non-specular bounce also
+// sends a shadow ray to a light, combined with material sampling by multiple
+// importance sampling. The default.
+pub struct PathTracer;
+
+impl PathTracer {
+    // `bsdf_pdf` is the density with which the previous bounce picked `r`,
+    // or None for camera rays and specular bounces. When it is set, light
+    // sampling at that bounce could also have found whatever emitter `r`
+    // hits, so the emission is MIS-weighted instead of counted in full.
+    fn ray_color(&self, r: &Ray, depth: i64, ctx: &RenderContext, bsdf_pdf: Option<f64>) -> Vec3 {
+        if depth <= 0 {
+            return black()
+        }
+        // Escaping rays count as emission too: an environment map among the
+        // lights is sampled like any other light.
+        let rec = hit(ctx.world, r);
+        let mut color_from_emission = match &rec {
+            Some(rec) => rec.mat.emitted(r, rec),
+            None => ctx.background.value(r),
+        };
+        if let Some(bsdf_pdf) = bsdf_pdf && !color_from_emission.near_zero() {
+            let light_pdf = ctx.lights.pdf_value(r.origin(), r.direction());
+            color_from_emission = color_from_emission.scalar_mul(power_heuristic(bsdf_pdf, light_pdf));
+        }
+        let Some(rec) = rec else {
+            return color_from_emission
+        };
+
+        let Some(srec) = rec.mat.scatter(r, &rec) else {
+            return color_from_emission
+        };
+
+        let pdf = match &srec.direction {
+            ScatterDirection::Specular(scattered) => {
+                let color_from_scatter = srec.attenuation * self.ray_color(scattered, depth - 1, ctx, None);
+                return color_from_emission + color_from_scatter
+            }
+            ScatterDirection::Sampled(pdf) => pdf.as_ref(),
+        };
+
+        let scattered = Ray::with_time(rec.p, pdf.generate(), r.time());
+        let pdf_value = pdf.value(scattered.direction());
+        if pdf_value <= 0.0 {
+            return color_from_emission
+        }
+        let bsdf_cos = rec.mat.eval(r, &rec, &srec, &scattered);
+
+        let sample_color = self.ray_color(&scattered, depth - 1, ctx, Some(pdf_value));
+        let color_from_scatter = (bsdf_cos * sample_color).scalar_div(pdf_value);
+
+        let color_from_lights = sample_lights(r, &rec, &srec, Some(pdf), ctx);
+        color_from_emission + (color_from_lights + color_from_scatter)
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, r: &Ray, ctx: &RenderContext) -> Vec3 {
+        self.ray_color(r, ctx.max_depth, ctx, None)
+    }
+}
+
+// Whitted-style ray tracing: direct light from the lights at rough
+// surfaces, and recursion only through mirrors and glass. No indirect
+// diffuse light, so it is fast and noise-free but not physically complete.
+pub struct Whitted;
+
+impl Whitted {
+    fn ray_color(&self, r: &Ray, depth: i64, ctx: &RenderContext) -> Vec3 {
+        if depth <= 0 {
+            return black()
+        }
+        let Some(rec) = hit(ctx.world, r) else {
+            return ctx.background.value(r)
+        };
+        let color_from_emission = rec.mat.emitted(r, &rec);
+        let Some(srec) = rec.mat.scatter(r, &rec) else {
+            return color_from_emission
+        };
+
+        let color = match &srec.direction {
+            ScatterDirection::Specular(scattered) => {
+                srec.attenuation * self.ray_color(scattered, depth - 1, ctx)
+            }
+            ScatterDirection::Sampled(_) => sample_lights(r, &rec, &srec, None, ctx),
+        };
+        color_from_emission + color
+    }
+}
+
+impl Integrator for Whitted {
+    fn radiance(&self, r: &Ray, ctx: &RenderContext) -> Vec3 {
+        self.ray_color(r, ctx.max_depth, ctx)
+    }
+}
+
+// The fraction of the hemisphere above the first surface hit that is open
+// within `max_distance`, in grey. Misses are black.
+pub struct AmbientOcclusion {
+    pub max_distance: f64,
+}
+
+impl AmbientOcclusion {
+    pub fn new(max_distance: f64) -> AmbientOcclusion {
+        AmbientOcclusion { max_distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, r: &Ray, ctx: &RenderContext) -> Vec3 {
+        let Some(rec) = hit(ctx.world, r) else {
+            return black()
+        };
+        // Cosine-weighted directions make the estimate a plain hit count.
+        let direction = Onb::new(rec.normal).transform(random_cosine_direction());
+        let probe = Ray::with_time(rec.p, direction, r.time());
+        let mut occluder = HitRecord::new();
+        if ctx.world.hit(&probe, Interval::new(0.001, self.max_distance), &mut occluder) {
+            black()
+        } else {
+            Vec3::new(1.0, 1.0, 1.0)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugChannel {
+    // Outward surface normals, mapped from [-1, 1] to [0, 1].
+    Normals,
+    // The colour the material scatters, or emits if it only emits.
+    Albedo,
+}
+
+// Shows one property of the first surface hit, for checking geometry and
+// materials or feeding a denoiser. Misses are black.
+pub struct DebugIntegrator {
+    pub channel: DebugChannel,
+}
+
+impl DebugIntegrator {
+    pub fn new(channel: DebugChannel) -> DebugIntegrator {
+        DebugIntegrator { channel }
+    }
+}
+
+impl Integrator for DebugIntegrator {
+    fn radiance(&self, r: &Ray, ctx: &RenderContext) -> Vec3 {
+        let Some(rec) = hit(ctx.world, r) else {
+            return black()
+        };
+        match self.channel {
+            DebugChannel::Normals => {
+                let outward = if rec.front_face { rec.normal } else { rec.normal.scalar_mul(-1.0) };
+                (outward + Vec3::new(1.0, 1.0, 1.0)).scalar_mul(0.5)
+            }
+            DebugChannel::Albedo => match rec.mat.scatter(r, &rec) {
+                Some(srec) => srec.attenuation,
+                None => rec.mat.emitted(r, &rec),
+            },
+        }
+    }
+}
+
+pub(crate) fn hit(world: &dyn Hittable, r: &Ray) -> Option<HitRecord> {
+    let mut rec = HitRecord::new();
+    if world.hit(r, Interval::new(0.001, f64::INFINITY), &mut rec) { Some(rec) } else { None }
+}
+
+pub(crate) fn black() -> Vec3 {
+    Vec3::new(0.0, 0.0, 0.0)
+}
+
+// Next-event estimation: one shadow ray towards a random light. With the
+// material's own sampling `bsdf`, area lights are MIS-weighted against it;
+// delta lights can only be found this way and count in full.
+fn sample_lights(r: &Ray, rec: &HitRecord, srec: &ScatterRecord, bsdf: Option<&dyn Pdf>, ctx: &RenderContext) -> Vec3 {
+    let Some(sample) = ctx.lights.sample(rec.p) else {
+        return black()
+    };
+    let (direction, light_pdf_value) = match sample {
+        LightSample::Emitter { direction, pdf } if pdf > 0.0 => (direction, pdf),
+        LightSample::Emitter { .. } => return black(),
+        LightSample::Delta { direction, distance, irradiance } => {
+            let shadow_ray = Ray::with_time(rec.p, direction, r.time());
+            let bsdf_cos = rec.mat.eval(r, rec, srec, &shadow_ray);
+            let mut occluder = HitRecord::new();
+            if bsdf_cos.near_zero() || ctx.world.hit(&shadow_ray, Interval::new(0.001, distance - 0.001), &mut occluder) {
+                return black()
+            }
+            return bsdf_cos * irradiance
+        }
+    };
+    let shadow_ray = Ray::with_time(rec.p, direction, r.time());
+    let bsdf_cos = rec.mat.eval(r, rec, srec, &shadow_ray);
+    if bsdf_cos.near_zero() {
+        return black()
+    }
+
+    // Whatever the shadow ray hits first is what is visible; an occluder
+    // simply emits nothing.
+    let emitted = match hit(ctx.world, &shadow_ray) {
+        Some(light_rec) => light_rec.mat.emitted(&shadow_ray, &light_rec),
+        None => ctx.background.value(&shadow_ray),
+    };
+
+    let weight = bsdf.map_or(1.0, |bsdf| power_heuristic(light_pdf_value, bsdf.value(shadow_ray.direction())));
+    (bsdf_cos * emitted).scalar_mul(weight / light_pdf_value)
+}
+
+pub(crate) fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 { a / (a + b) } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::camera::CameraSettings;
+    use crate::hittable_list::HittableList;
+    use crate::image::Image;
+    use crate::light::{AreaLight, LightList};
+    use crate::material::Lambertian;
+    use crate::plane::Plane;
+    use crate::quad::Quad;
+    use crate::vec3::Point3;
+
+    fn render(world: &HittableList, lights: &LightList, lookfrom: Point3, integrator: Box<dyn Integrator>) -> Image {
+        let mut camera = Camera::new(CameraSettings {
+            aspect_ratio: 1.0,
+            image_width: 16,
+            samples_per_pixel: 256,
+            max_depth: 16,
+            vfov: 40,
+            lookfrom,
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            ..CameraSettings::default()
+        });
+        camera.background = Background::black();
+        camera.seed = Some(7);
+        camera.integrator = integrator;
+        camera.render(world, lights)
+    }
+
+    fn mean(image: &Image) -> Vec3 {
+        let sum = image.pixels.iter().fold(black(), |sum, c| sum + *c);
+        sum.scalar_div(image.pixels.len() as f64)
+    }
+
+    #[test]
+    fn naive_and_next_event_path_tracing_agree() {
+        // A grey floor lit by a quad facing down at it, just above the view.
+        let mut world = HittableList::new();
+        let floor = Lambertian::new(Vec3::new(0.5, 0.5, 0.5));
+        world.add(Arc::new(Quad::new(Point3::new(-3.0, 0.0, -3.0), Vec3::new(6.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 6.0), Arc::new(floor))));
+        let light = Arc::new(AreaLight::rect(Point3::new(-1.0, 1.5, -1.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0), Vec3::new(1.0, 1.0, 1.0), 20.0));
+        world.add(light.shape.clone());
+        let mut lights = LightList::new();
+        lights.add(light);
+
+        let lookfrom = Point3::new(0.0, 1.0, 3.0);
+        let naive = mean(&render(&world, &lights, lookfrom, Box::new(NaivePathTracer)));
+        let nee = mean(&render(&world, &lights, lookfrom, Box::new(PathTracer)));
+        assert!(nee.x() > 0.0);
+        assert!((naive.x() / nee.x() - 1.0).abs() < 0.05, "naive {:?}, next-event {:?}", naive, nee);
+    }
+
+    #[test]
+    fn ambient_occlusion_of_an_open_plane_is_one() {
+        let mut world = HittableList::new();
+        let grey = Lambertian::new(Vec3::new(0.5, 0.5, 0.5));
+        world.add(Arc::new(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Arc::new(grey))));
+
+        // Looking down, so every camera ray hits the plane.
+        let image = render(&world, &LightList::new(), Point3::new(0.0, 3.0, 1.0), Box::new(AmbientOcclusion::new(10.0)));
+        for color in &image.pixels {
+            assert_eq!(*color, Vec3::new(1.0, 1.0, 1.0));
+        }
+    }
+}
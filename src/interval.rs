@@ -1,5 +1,4 @@
-use std::f64::INFINITY;
-
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Interval{
     pub min : f64,
     pub max : f64,
@@ -8,20 +7,24 @@ pub struct Interval{
 impl Interval{
     pub const fn new(min: f64, max: f64) -> Interval{
         Interval{
-            min: min,
-            max: max,
+            min,
+            max,
         }
     }
 
-    pub const EMPTY: Interval = Interval::new(INFINITY, -1.0 * INFINITY);
-    pub const _UNIVERSE: Self = Self::new(-1.0 * INFINITY, INFINITY);
+    pub fn enclosing(a: &Interval, b: &Interval) -> Interval {
+        Interval::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
+    pub const EMPTY: Interval = Interval::new(f64::INFINITY, f64::NEG_INFINITY);
+    pub const UNIVERSE: Self = Self::new(f64::NEG_INFINITY, f64::INFINITY);
 
-    pub fn _size( x: Interval ) -> f64 {
-        x.max - x.min
+    pub fn size(&self) -> f64 {
+        self.max - self.min
     }
 
-    pub fn _contains(x: Interval ,y: f64 ) -> bool {
-        x.min <= y && y <= x.max
+    pub fn contains(&self, y: f64 ) -> bool {
+        self.min <= y && y <= self.max
     }
 
     pub fn surrounds ( x: &Interval , y: f64 ) -> bool {
@@ -35,7 +38,23 @@ impl Interval{
         if y > x.max{
             return x.max
         }
-        return y
+        y
+    }
+
+    pub fn expand(&self, delta: f64) -> Interval {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
+    // Midpoint used when sorting primitives; unbounded sides collapse to the
+    // finite one (or 0) so infinite objects still get a usable key.
+    pub fn center(&self) -> f64 {
+        match (self.min.is_finite(), self.max.is_finite()) {
+            (true, true) => 0.5 * (self.min + self.max),
+            (true, false) => self.min,
+            (false, true) => self.max,
+            (false, false) => 0.0,
+        }
     }
 }
 
@@ -43,4 +62,4 @@ impl Default for Interval {
     fn default() -> Self {
         Self::EMPTY
     }
-}
\ No newline at end of file
+}
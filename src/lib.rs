@@ -0,0 +1,82 @@
+//! A CPU path tracer: vector math, geometry, materials, acceleration
+//! structures, scene loading and image output. `Camera::render` is the entry
+//! point; the `render` example is a command-line front end over it.
+
+pub mod aabb;
+pub mod background;
+pub mod bdpt;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod constant_medium;
+pub mod environment;
+pub mod hittable;
+pub mod hittable_list;
+pub mod image;
+pub mod integrator;
+pub mod interval;
+pub mod light;
+pub mod light_bvh;
+pub mod mat4;
+pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod onb;
+pub mod output;
+pub mod pbr;
+pub mod pdf;
+pub mod perlin;
+pub mod plane;
+pub mod quad;
+pub mod ray;
+pub mod scene;
+pub mod sky;
+pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
+pub mod vec3;
+
+use std::{cell::RefCell, f64::consts::PI};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+pub use crate::{
+    background::Background,
+    bdpt::BidirectionalPathTracer,
+    bvh::BvhNode,
+    camera::{Camera, CameraSettings},
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    image::Image,
+    integrator::{AmbientOcclusion, DebugChannel, DebugIntegrator, Integrator, NaivePathTracer, PathTracer, Whitted},
+    light::{AreaLight, DirectionalLight, Light, LightBounds, LightList, PointLight, SpotLight},
+    light_bvh::{LightBvh, PowerLightSampler},
+    material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal, ScatterRecord},
+    pbr::Pbr,
+    ray::Ray,
+    texture::Texture,
+    vec3::{Point3, Vec3},
+};
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+pub fn degrees_to_radians (degrees : f64) -> f64 {
+    (degrees * PI) / 180.0
+}
+
+// Uniform random number in [0, 1) from the calling thread's generator.
+pub fn random_double() -> f64 {
+    RNG.with(|rng| rng.borrow_mut().r#gen())
+}
+
+pub fn random_double_x(min: f64,max: f64) -> f64 {
+    min + (max - min) * random_double()
+}
+
+// Reseeds the calling thread's generator so the following samples are
+// reproducible.
+pub fn seed_thread_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
@@ -0,0 +1,495 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::DiffuseLight;
+use crate::quad::Quad;
+use crate::random_double;
+use crate::ray::Ray;
+use crate::sphere::Sphere;
+use crate::vec3::{Point3, Vec3};
+
+// Light power is in watts and lengths in scene units, taken as metres, so
+// radiance comes out in W/(sr·m²). Colours tint a light without being
+// normalised.
+
+pub enum LightSample {
+    // A direction towards an emitter that is part of the world or the
+    // background. The shadow ray finds what is actually visible there and
+    // its radiance; `pdf` is the solid-angle density of the direction.
+    Emitter { direction: Vec3, pdf: f64 },
+    // Light arriving from exactly one direction, from a source no ray can
+    // hit. `irradiance` is what it delivers to a surface facing it, and
+    // anything closer than `distance` blocks it.
+    Delta { direction: Vec3, distance: f64, irradiance: Vec3 },
+}
+
+pub trait Light: Send + Sync {
+    // None if the light can't reach `origin` at all.
+    fn sample(&self, origin: Point3) -> Option<LightSample>;
+
+    // Solid-angle density with which `sample` picks `direction` from
+    // `origin`. Delta lights can't be found by other sampling and keep the
+    // zero default.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    // Where the light is, where it shines and how much it emits, so that
+    // samplers can favour the lights that matter at a point. Lights at
+    // infinity have no bounds and are chosen apart from the rest.
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+
+    // A point to start a light path from, picked without regard to any
+    // shading point, for bidirectional integrators. Lights at infinity
+    // can't start paths and keep the None default.
+    fn sample_point(&self) -> Option<LightPoint<'_>> {
+        None
+    }
+
+    // The density per unit area with which `sample_point` picks `p`, a
+    // point on an emissive surface seen from `origin`. Zero if `p` isn't on
+    // this light.
+    fn point_pdf(&self, _origin: Point3, _p: Point3) -> f64 {
+        0.0
+    }
+
+    // Radiant intensity towards `direction`, for lights that emit from a
+    // single point.
+    fn intensity(&self, _direction: Vec3) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+}
+
+// Where a light path starts.
+pub struct LightPoint<'a> {
+    pub p: Point3,
+    // Density of having picked `p`, including the choice of light: per unit
+    // area on a surface, or the probability of the light for point lights.
+    pub pdf: f64,
+    pub source: LightSource<'a>,
+}
+
+pub enum LightSource<'a> {
+    // A point on an emissive surface, as a hit record with the outward
+    // normal. Its material gives the radiance.
+    Surface(HitRecord),
+    // A light at a single point, whose `intensity` gives the emission.
+    Point(&'a dyn Light),
+}
+
+// A light or group of lights summarised for sampling: total power `phi`
+// inside `bounds`, emitted around the cone of normals with axis `w` and
+// half-angle acos(cos_theta_o), plus up to acos(cos_theta_e) beyond it.
+#[derive(Clone, Copy, Debug)]
+pub struct LightBounds {
+    pub bounds: Aabb,
+    pub phi: f64,
+    pub w: Vec3,
+    pub cos_theta_o: f64,
+    pub cos_theta_e: f64,
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    pub fn new(bounds: Aabb, phi: f64, w: Vec3, cos_theta_o: f64, cos_theta_e: f64, two_sided: bool) -> LightBounds {
+        LightBounds { bounds, phi, w: w.unit_vector(), cos_theta_o, cos_theta_e, two_sided }
+    }
+
+    // Emits in every direction, like a point or a sphere.
+    pub fn omnidirectional(bounds: Aabb, phi: f64) -> LightBounds {
+        LightBounds::new(bounds, phi, Vec3::new(0.0, 0.0, 1.0), -1.0, 0.0, false)
+    }
+
+    // A flat emitter facing `normal`.
+    pub fn planar(bounds: Aabb, phi: f64, normal: Vec3, two_sided: bool) -> LightBounds {
+        LightBounds::new(bounds, phi, normal, 1.0, 0.0, two_sided)
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Vec3::new(self.bounds.centroid(0), self.bounds.centroid(1), self.bounds.centroid(2))
+    }
+
+    // Estimated contribution to point `p` (Conty Estevez and Kulla, 2018):
+    // power over squared distance, scaled by how far `p` lies outside the
+    // emission cone as seen across the whole box. Zero only where no light
+    // inside can reach.
+    pub fn importance(&self, p: Point3) -> f64 {
+        let pc = self.centroid();
+        let diagonal = Vec3::new(self.bounds.x.size(), self.bounds.y.size(), self.bounds.z.size()).length();
+        let d2 = (p - pc).length_squared().max(diagonal / 2.0);
+
+        // Half-angle of the cone around the box's bounding sphere seen from p.
+        let radius = diagonal / 2.0;
+        let distance_squared = (p - pc).length_squared();
+        if distance_squared <= radius * radius {
+            return self.phi / d2
+        }
+        let cos_theta_b = (1.0 - radius * radius / distance_squared).max(0.0).sqrt();
+        let sin_theta_b = safe_sqrt(1.0 - cos_theta_b * cos_theta_b);
+
+        let wi = (p - pc).unit_vector();
+        let mut cos_theta_w = Vec3::dot_product(self.w, wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let sin_theta_w = safe_sqrt(1.0 - cos_theta_w * cos_theta_w);
+        let sin_theta_o = safe_sqrt(1.0 - self.cos_theta_o * self.cos_theta_o);
+
+        // Angle from the cone to p, less the spread of the box.
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p < self.cos_theta_e {
+            return 0.0
+        }
+        (self.phi * cos_theta_p / d2).max(0.0)
+    }
+
+    pub fn union(a: &LightBounds, b: &LightBounds) -> LightBounds {
+        if a.phi == 0.0 {
+            return *b
+        }
+        if b.phi == 0.0 {
+            return *a
+        }
+        let (w, cos_theta_o) = union_cones(a.w, a.cos_theta_o, b.w, b.cos_theta_o);
+        LightBounds {
+            bounds: Aabb::enclosing(&a.bounds, &b.bounds),
+            phi: a.phi + b.phi,
+            w,
+            cos_theta_o,
+            cos_theta_e: a.cos_theta_e.min(b.cos_theta_e),
+            two_sided: a.two_sided || b.two_sided,
+        }
+    }
+}
+
+// Smallest cone containing both cones, each given by axis and cos of the
+// half-angle.
+fn union_cones(wa: Vec3, cos_a: f64, wb: Vec3, cos_b: f64) -> (Vec3, f64) {
+    let theta_a = cos_a.clamp(-1.0, 1.0).acos();
+    let theta_b = cos_b.clamp(-1.0, 1.0).acos();
+    let theta_d = Vec3::dot_product(wa, wb).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return (wa, cos_a)
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return (wb, cos_b)
+    }
+
+    let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+    let axis = Vec3::cross_product(wa, wb);
+    if theta_o >= PI || axis.length_squared() == 0.0 {
+        return (wa, -1.0)
+    }
+    // Rotate wa towards wb by theta_o - theta_a (Rodrigues' formula).
+    let k = axis.unit_vector();
+    let (sin_r, cos_r) = (theta_o - theta_a).sin_cos();
+    let w = wa.scalar_mul(cos_r) + Vec3::cross_product(k, wa).scalar_mul(sin_r) + k.scalar_mul(Vec3::dot_product(k, wa) * (1.0 - cos_r));
+    (w.unit_vector(), theta_o.cos())
+}
+
+// cos(a - b) and sin(a - b), clamped to zero difference when a < b.
+fn cos_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b { 1.0 } else { cos_a * cos_b + sin_a * sin_b }
+}
+
+fn sin_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b { 0.0 } else { sin_a * cos_b - cos_a * sin_b }
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.0).sqrt()
+}
+
+#[derive(Default)]
+pub struct LightList {
+    pub lights: Vec<Arc<dyn Light>>,
+}
+
+impl LightList {
+    pub fn new() -> LightList {
+        LightList { lights: Vec::new() }
+    }
+
+    pub fn add(&mut self, light: Arc<dyn Light>) {
+        self.lights.push(light);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+}
+
+// Picks one light uniformly, so densities are averaged and delta samples
+// are scaled up by the number of lights.
+impl Light for LightList {
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let size = self.lights.len();
+        if size == 0 {
+            return None
+        }
+        let index = ((random_double() * size as f64) as usize).min(size - 1);
+        Some(match self.lights[index].sample(origin)? {
+            LightSample::Emitter { direction, .. } => {
+                LightSample::Emitter { direction, pdf: self.pdf_value(origin, direction) }
+            }
+            LightSample::Delta { direction, distance, irradiance } => {
+                LightSample::Delta { direction, distance, irradiance: irradiance.scalar_mul(size as f64) }
+            }
+        })
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0
+        }
+        let sum: f64 = self.lights.iter().map(|light| light.pdf_value(origin, direction)).sum();
+        sum / self.lights.len() as f64
+    }
+
+    fn sample_point(&self) -> Option<LightPoint<'_>> {
+        let size = self.lights.len();
+        if size == 0 {
+            return None
+        }
+        let index = ((random_double() * size as f64) as usize).min(size - 1);
+        let mut point = self.lights[index].sample_point()?;
+        point.pdf /= size as f64;
+        Some(point)
+    }
+
+    fn point_pdf(&self, origin: Point3, p: Point3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0
+        }
+        let sum: f64 = self.lights.iter().map(|light| light.point_pdf(origin, p)).sum();
+        sum / self.lights.len() as f64
+    }
+}
+
+// An emissive object in the world, sampled through `Hittable::random` and
+// `pdf_value`. The object must be in the world as well.
+pub struct AreaLight {
+    pub shape: Arc<dyn Hittable>,
+    pub bounds: LightBounds,
+    // 1/area, or zero if the shape can't be sampled by area.
+    area_pdf: f64,
+}
+
+impl AreaLight {
+    // Without knowing the emitter, its bounds assume one watt in every
+    // direction; use `with_bounds` when mixing it with other lights.
+    pub fn new(shape: Arc<dyn Hittable>) -> AreaLight {
+        let bounds = LightBounds::omnidirectional(shape.bounding_box(), 1.0);
+        AreaLight::with_bounds(shape, bounds)
+    }
+
+    pub fn with_bounds(shape: Arc<dyn Hittable>, bounds: LightBounds) -> AreaLight {
+        let area_pdf = shape.sample_surface().map_or(0.0, |(_, pdf)| pdf);
+        AreaLight { shape, bounds, area_pdf }
+    }
+
+    // A rectangle emitting `power` watts from the side `u × v` points to.
+    pub fn rect(corner: Point3, u: Vec3, v: Vec3, color: Vec3, power: f64) -> AreaLight {
+        let area = Vec3::cross_product(u, v).length();
+        let radiance = color.scalar_mul(power / (PI * area));
+        let shape: Arc<dyn Hittable> = Arc::new(Quad::new(corner, u, v, Arc::new(DiffuseLight::one_sided(radiance))));
+        let bounds = LightBounds::planar(shape.bounding_box(), max_component(color) * power, Vec3::cross_product(u, v), false);
+        AreaLight::with_bounds(shape, bounds)
+    }
+
+    // A sphere emitting `power` watts outwards.
+    pub fn sphere(center: Point3, radius: f64, color: Vec3, power: f64) -> AreaLight {
+        let area = 4.0 * PI * radius * radius;
+        let radiance = color.scalar_mul(power / (PI * area));
+        let shape: Arc<dyn Hittable> = Arc::new(Sphere::new(center, radius, Arc::new(DiffuseLight::one_sided(radiance))));
+        let bounds = LightBounds::omnidirectional(shape.bounding_box(), max_component(color) * power);
+        AreaLight::with_bounds(shape, bounds)
+    }
+}
+
+impl Light for AreaLight {
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let direction = self.shape.random(origin);
+        let pdf = self.shape.pdf_value(origin, direction);
+        if pdf <= 0.0 {
+            return None
+        }
+        Some(LightSample::Emitter { direction, pdf })
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.shape.pdf_value(origin, direction)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(self.bounds)
+    }
+
+    fn sample_point(&self) -> Option<LightPoint<'_>> {
+        let (rec, pdf) = self.shape.sample_surface()?;
+        Some(LightPoint { p: rec.p, pdf, source: LightSource::Surface(rec) })
+    }
+
+    fn point_pdf(&self, origin: Point3, p: Point3) -> f64 {
+        // `p` is on this light only if the ray towards it first meets the
+        // shape right there (t = 1).
+        let mut rec = HitRecord::new();
+        let ray = Ray::new(origin, p - origin);
+        if !self.shape.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec) || (rec.t - 1.0).abs() > 1e-4 {
+            return 0.0
+        }
+        self.area_pdf
+    }
+}
+
+// Emits equally in all directions from a single point.
+pub struct PointLight {
+    pub position: Point3,
+    // Radiant intensity in W/sr.
+    pub intensity: Vec3,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, color: Vec3, power: f64) -> PointLight {
+        PointLight { position, intensity: color.scalar_mul(power / (4.0 * PI)) }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let (direction, distance) = towards(origin, self.position)?;
+        let irradiance = self.intensity.scalar_div(distance * distance);
+        Some(LightSample::Delta { direction, distance, irradiance })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = 4.0 * PI * max_component(self.intensity);
+        Some(LightBounds::omnidirectional(Aabb::from_points(self.position, self.position), phi))
+    }
+
+    fn sample_point(&self) -> Option<LightPoint<'_>> {
+        Some(LightPoint { p: self.position, pdf: 1.0, source: LightSource::Point(self) })
+    }
+
+    fn intensity(&self, _direction: Vec3) -> Vec3 {
+        self.intensity
+    }
+}
+
+// A point light restricted to a cone. Full intensity inside
+// `cos_inner`, fading smoothly to nothing at `cos_outer`.
+pub struct SpotLight {
+    pub position: Point3,
+    pub direction: Vec3,
+    // Radiant intensity on the axis, in W/sr.
+    pub intensity: Vec3,
+    pub cos_inner: f64,
+    pub cos_outer: f64,
+}
+
+impl SpotLight {
+    // The angles are measured from the axis, in degrees. `power` is the
+    // radiant flux in watts leaving through the cone, falloff included, so
+    // narrowing the cone makes it brighter.
+    pub fn new(position: Point3, direction: Vec3, color: Vec3, power: f64, inner_angle: f64, outer_angle: f64) -> SpotLight {
+        let cos_inner = crate::degrees_to_radians(inner_angle).cos();
+        let cos_outer = crate::degrees_to_radians(outer_angle).cos();
+        let solid_angle = cone_solid_angle(cos_inner, cos_outer);
+        let intensity = if solid_angle > 0.0 { power / solid_angle } else { 0.0 };
+        SpotLight {
+            position,
+            direction: direction.unit_vector(),
+            intensity: color.scalar_mul(intensity),
+            cos_inner,
+            cos_outer,
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.0
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let (direction, distance) = towards(origin, self.position)?;
+        let falloff = self.falloff(-Vec3::dot_product(direction, self.direction));
+        if falloff <= 0.0 {
+            return None
+        }
+        let irradiance = self.intensity.scalar_mul(falloff / (distance * distance));
+        Some(LightSample::Delta { direction, distance, irradiance })
+    }
+
+    // The cone covers everything lit, so no spread beyond it is needed.
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = cone_solid_angle(self.cos_inner, self.cos_outer) * max_component(self.intensity);
+        let bounds = Aabb::from_points(self.position, self.position);
+        Some(LightBounds::new(bounds, phi, self.direction, self.cos_outer, 1.0, false))
+    }
+
+    fn sample_point(&self) -> Option<LightPoint<'_>> {
+        Some(LightPoint { p: self.position, pdf: 1.0, source: LightSource::Point(self) })
+    }
+
+    fn intensity(&self, direction: Vec3) -> Vec3 {
+        self.intensity.scalar_mul(self.falloff(Vec3::dot_product(direction.unit_vector(), self.direction)))
+    }
+}
+
+// Parallel light from infinitely far away, like the sun without its disk.
+pub struct DirectionalLight {
+    // The direction the light travels in.
+    pub direction: Vec3,
+    // Irradiance on a surface facing the light, in W/m².
+    pub irradiance: Vec3,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, color: Vec3, irradiance: f64) -> DirectionalLight {
+        DirectionalLight { direction: direction.unit_vector(), irradiance: color.scalar_mul(irradiance) }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _origin: Point3) -> Option<LightSample> {
+        Some(LightSample::Delta {
+            direction: self.direction.scalar_mul(-1.0),
+            distance: f64::INFINITY,
+            irradiance: self.irradiance,
+        })
+    }
+}
+
+// The integral of the spot falloff over the sphere: the full-intensity cap
+// out to `cos_inner`, plus the smoothstep ring, which averages one half.
+fn cone_solid_angle(cos_inner: f64, cos_outer: f64) -> f64 {
+    2.0 * PI * (1.0 - 0.5 * (cos_inner + cos_outer))
+}
+
+fn max_component(v: Vec3) -> f64 {
+    v.x().max(v.y()).max(v.z())
+}
+
+fn towards(origin: Point3, position: Point3) -> Option<(Vec3, f64)> {
+    let offset = position - origin;
+    let distance = offset.length();
+    if distance <= 0.0 {
+        return None
+    }
+    Some((offset.scalar_div(distance), distance))
+}
@@ -0,0 +1,457 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::light::{Light, LightBounds, LightList, LightPoint, LightSample};
+use crate::random_double;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+const SAOH_BUCKETS: usize = 12;
+
+// Chooses among many lights in proportion to their estimated contribution
+// at the shading point, by walking a hierarchy of light bounds (Conty
+// Estevez and Kulla, 2018). Lights without bounds, such as environment maps
+// and the sun, each get the same chance as the whole tree. Light paths,
+// which have no shading point, start from lights chosen by power.
+pub struct LightBvh {
+    lights: Vec<Arc<dyn Light>>,
+    infinite: Vec<Arc<dyn Light>>,
+    nodes: Vec<LightNode>,
+    root: Option<usize>,
+    power: PowerDistribution,
+}
+
+enum LightNode {
+    Leaf { bounds: LightBounds, light: usize },
+    Interior { bounds: LightBounds, children: [usize; 2] },
+}
+
+impl LightNode {
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            LightNode::Leaf { bounds, .. } | LightNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+impl LightBvh {
+    pub fn new(list: LightList) -> LightBvh {
+        let (lights, infinite, mut items) = split_bounded(list);
+        let power = PowerDistribution::new(&items);
+        // Lights that emit nothing are never worth a sample.
+        items.retain(|(_, bounds)| bounds.phi > 0.0);
+
+        let mut bvh = LightBvh { lights, infinite, nodes: Vec::new(), root: None, power };
+        if !items.is_empty() {
+            bvh.root = Some(bvh.build(&mut items));
+        }
+        bvh
+    }
+
+    fn build(&mut self, items: &mut [(usize, LightBounds)]) -> usize {
+        if let [(light, bounds)] = items {
+            self.nodes.push(LightNode::Leaf { bounds: *bounds, light: *light });
+            return self.nodes.len() - 1
+        }
+
+        let bounds = items.iter().skip(1).fold(items[0].1, |acc, (_, b)| LightBounds::union(&acc, b));
+        let mid = partition(items);
+        let (left_items, right_items) = items.split_at_mut(mid);
+        let left = self.build(left_items);
+        let right = self.build(right_items);
+        self.nodes.push(LightNode::Interior { bounds, children: [left, right] });
+        self.nodes.len() - 1
+    }
+
+    fn infinite_probability(&self) -> f64 {
+        let bounded = if self.root.is_some() { 1.0 } else { 0.0 };
+        let count = self.infinite.len() as f64;
+        if count == 0.0 { 0.0 } else { count / (count + bounded) }
+    }
+
+    // Probability of picking each light whose bounds the ray from `origin`
+    // passes through, times that light's density for `direction`. Other
+    // lights can't be hit in that direction.
+    fn tree_pdf(&self, node: usize, pmf: f64, origin: Point3, direction: Vec3, ray: &Ray) -> f64 {
+        match &self.nodes[node] {
+            LightNode::Leaf { bounds, light } => {
+                if bounds.importance(origin) <= 0.0 {
+                    return 0.0
+                }
+                pmf * self.lights[*light].pdf_value(origin, direction)
+            }
+            LightNode::Interior { children, .. } => {
+                let importance = children.map(|child| self.nodes[child].bounds().importance(origin));
+                let total = importance[0] + importance[1];
+                if total <= 0.0 {
+                    return 0.0
+                }
+                let mut pdf = 0.0;
+                for (child, weight) in children.iter().zip(importance) {
+                    if weight > 0.0 && self.nodes[*child].bounds().bounds.hit(ray, Interval::new(0.0, f64::INFINITY)) {
+                        pdf += self.tree_pdf(*child, pmf * weight / total, origin, direction, ray);
+                    }
+                }
+                pdf
+            }
+        }
+    }
+
+    fn sample_tree(&self, origin: Point3) -> Option<(usize, f64)> {
+        let mut node = self.root?;
+        let mut pmf = 1.0;
+        loop {
+            match &self.nodes[node] {
+                LightNode::Leaf { bounds, light } => {
+                    return if bounds.importance(origin) > 0.0 { Some((*light, pmf)) } else { None }
+                }
+                LightNode::Interior { children, .. } => {
+                    let importance = children.map(|child| self.nodes[child].bounds().importance(origin));
+                    let total = importance[0] + importance[1];
+                    if total <= 0.0 {
+                        return None
+                    }
+                    let left = importance[0] / total;
+                    if random_double() < left {
+                        node = children[0];
+                        pmf *= left;
+                    } else {
+                        node = children[1];
+                        pmf *= 1.0 - left;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Light for LightBvh {
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let p_infinite = self.infinite_probability();
+        let (light, pmf) = if random_double() < p_infinite {
+            let index = ((random_double() * self.infinite.len() as f64) as usize).min(self.infinite.len() - 1);
+            (&self.infinite[index], p_infinite / self.infinite.len() as f64)
+        } else {
+            let (index, pmf) = self.sample_tree(origin)?;
+            (&self.lights[index], pmf * (1.0 - p_infinite))
+        };
+        scale_sample(light.sample(origin)?, pmf, |direction| self.pdf_value(origin, direction))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let p_infinite = self.infinite_probability();
+        let mut pdf = 0.0;
+        for light in &self.infinite {
+            pdf += p_infinite / self.infinite.len() as f64 * light.pdf_value(origin, direction);
+        }
+        if let Some(root) = self.root {
+            let ray = Ray::new(origin, direction);
+            if self.nodes[root].bounds().bounds.hit(&ray, Interval::new(0.0, f64::INFINITY)) {
+                pdf += self.tree_pdf(root, 1.0 - p_infinite, origin, direction, &ray);
+            }
+        }
+        pdf
+    }
+
+    fn sample_point(&self) -> Option<LightPoint<'_>> {
+        self.power.sample_point(&self.lights)
+    }
+
+    fn point_pdf(&self, origin: Point3, p: Point3) -> f64 {
+        self.power.point_pdf(&self.lights, origin, p)
+    }
+}
+
+// The simple alternative to `LightBvh`: chooses lights in proportion to
+// their power alone, from a flat CDF, ignoring where the shading point is.
+// Lights without bounds are treated as in `LightBvh`.
+pub struct PowerLightSampler {
+    lights: Vec<Arc<dyn Light>>,
+    infinite: Vec<Arc<dyn Light>>,
+    power: PowerDistribution,
+}
+
+impl PowerLightSampler {
+    pub fn new(list: LightList) -> PowerLightSampler {
+        let (lights, infinite, items) = split_bounded(list);
+        let power = PowerDistribution::new(&items);
+        PowerLightSampler { lights, infinite, power }
+    }
+
+    fn infinite_probability(&self) -> f64 {
+        let bounded = if self.lights.is_empty() { 0.0 } else { 1.0 };
+        let count = self.infinite.len() as f64;
+        if count == 0.0 { 0.0 } else { count / (count + bounded) }
+    }
+}
+
+impl Light for PowerLightSampler {
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let p_infinite = self.infinite_probability();
+        let (light, pmf) = if random_double() < p_infinite {
+            let index = ((random_double() * self.infinite.len() as f64) as usize).min(self.infinite.len() - 1);
+            (&self.infinite[index], p_infinite / self.infinite.len() as f64)
+        } else {
+            let index = self.power.sample()?;
+            (&self.lights[index], self.power.probability(index) * (1.0 - p_infinite))
+        };
+        scale_sample(light.sample(origin)?, pmf, |direction| self.pdf_value(origin, direction))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let p_infinite = self.infinite_probability();
+        let infinite: f64 = self.infinite.iter()
+            .map(|light| p_infinite / self.infinite.len() as f64 * light.pdf_value(origin, direction))
+            .sum();
+        let bounded: f64 = self.lights.iter().enumerate()
+            .map(|(index, light)| (1.0 - p_infinite) * self.power.probability(index) * light.pdf_value(origin, direction))
+            .sum();
+        infinite + bounded
+    }
+
+    fn sample_point(&self) -> Option<LightPoint<'_>> {
+        self.power.sample_point(&self.lights)
+    }
+
+    fn point_pdf(&self, origin: Point3, p: Point3) -> f64 {
+        self.power.point_pdf(&self.lights, origin, p)
+    }
+}
+
+// A flat CDF over bounded lights by power, from their bounds.
+struct PowerDistribution {
+    // cdf[i] is the total power of lights[..i], normalised.
+    cdf: Vec<f64>,
+}
+
+impl PowerDistribution {
+    fn new(items: &[(usize, LightBounds)]) -> PowerDistribution {
+        let mut powers: Vec<f64> = items.iter().map(|(_, bounds)| bounds.phi.max(0.0)).collect();
+        let total: f64 = powers.iter().sum();
+        // If nothing reports any power, fall back to uniform choice.
+        if total <= 0.0 {
+            powers.iter_mut().for_each(|power| *power = 1.0);
+        }
+        let total: f64 = powers.iter().sum();
+
+        let mut cdf = Vec::with_capacity(powers.len() + 1);
+        let mut running = 0.0;
+        cdf.push(0.0);
+        for power in powers {
+            running += power;
+            cdf.push(running / total);
+        }
+        PowerDistribution { cdf }
+    }
+
+    fn sample(&self) -> Option<usize> {
+        let count = self.cdf.len() - 1;
+        if count == 0 {
+            return None
+        }
+        let x = random_double();
+        Some(self.cdf.partition_point(|&c| c <= x).clamp(1, count) - 1)
+    }
+
+    fn probability(&self, index: usize) -> f64 {
+        self.cdf[index + 1] - self.cdf[index]
+    }
+
+    fn sample_point<'a>(&self, lights: &'a [Arc<dyn Light>]) -> Option<LightPoint<'a>> {
+        let index = self.sample()?;
+        let mut point = lights[index].sample_point()?;
+        point.pdf *= self.probability(index);
+        Some(point)
+    }
+
+    fn point_pdf(&self, lights: &[Arc<dyn Light>], origin: Point3, p: Point3) -> f64 {
+        lights.iter().enumerate()
+            .map(|(index, light)| self.probability(index) * light.point_pdf(origin, p))
+            .sum()
+    }
+}
+
+type SplitLights = (Vec<Arc<dyn Light>>, Vec<Arc<dyn Light>>, Vec<(usize, LightBounds)>);
+
+// Separates lights with bounds (returned with their index and bounds) from
+// lights at infinity.
+fn split_bounded(list: LightList) -> SplitLights {
+    let mut lights = Vec::new();
+    let mut infinite = Vec::new();
+    let mut items = Vec::new();
+    for light in list.lights {
+        match light.bounds() {
+            Some(bounds) => {
+                items.push((lights.len(), bounds));
+                lights.push(light);
+            }
+            None => infinite.push(light),
+        }
+    }
+    (lights, infinite, items)
+}
+
+// A sample from a light picked with probability `pmf`. Emitter samples take
+// the density of the whole sampler, since any light could have produced the
+// direction; delta samples are divided by the chance of picking the light.
+fn scale_sample(sample: LightSample, pmf: f64, pdf_value: impl Fn(Vec3) -> f64) -> Option<LightSample> {
+    if pmf <= 0.0 {
+        return None
+    }
+    Some(match sample {
+        LightSample::Emitter { direction, .. } => LightSample::Emitter { direction, pdf: pdf_value(direction) },
+        LightSample::Delta { direction, distance, irradiance } => {
+            LightSample::Delta { direction, distance, irradiance: irradiance.scalar_div(pmf) }
+        }
+    })
+}
+
+// Splits `items` in place and returns the index of the first item on the
+// right. Uses the binned surface area orientation heuristic over all three
+// axes, falling back to a median split when the centroids coincide or every
+// split costs nothing (as for a cluster of point lights).
+fn partition(items: &mut [(usize, LightBounds)]) -> usize {
+    let mid = items.len() / 2;
+    let bounds = items.iter().fold(Aabb::EMPTY, |acc, (_, b)| Aabb::enclosing(&acc, &b.bounds));
+    let centroid_bounds = items.iter().fold(Aabb::EMPTY, |acc, (_, b)| {
+        let c = b.centroid();
+        Aabb::enclosing(&acc, &Aabb::new(
+            Interval::new(c.x(), c.x()),
+            Interval::new(c.y(), c.y()),
+            Interval::new(c.z(), c.z()),
+        ))
+    });
+    let max_extent = (0..3).map(|axis| bounds.axis_interval(axis).size()).fold(0.0, f64::max);
+
+    // (cost, axis, last bucket on the left)
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        let extent = *centroid_bounds.axis_interval(axis);
+        if extent.size() <= 0.0 {
+            continue
+        }
+        let mut buckets: [Option<LightBounds>; SAOH_BUCKETS] = [None; SAOH_BUCKETS];
+        for (_, b) in items.iter() {
+            let bucket = &mut buckets[bucket_of(b, axis, extent)];
+            *bucket = Some(match bucket {
+                Some(acc) => LightBounds::union(acc, b),
+                None => *b,
+            });
+        }
+
+        let kr = max_extent / bounds.axis_interval(axis).size();
+        let merge = |range: &[Option<LightBounds>]| {
+            range.iter().flatten().fold(None, |acc: Option<LightBounds>, b| {
+                Some(acc.map_or(*b, |acc| LightBounds::union(&acc, b)))
+            })
+        };
+        for split in 0..SAOH_BUCKETS - 1 {
+            let (Some(left), Some(right)) = (merge(&buckets[..=split]), merge(&buckets[split + 1..])) else {
+                continue
+            };
+            let cost = kr * (orientation_cost(&left) + orientation_cost(&right));
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, split));
+            }
+        }
+    }
+
+    let Some((_, axis, split)) = best.filter(|(cost, _, _)| *cost > 0.0 && cost.is_finite()) else {
+        let axis = centroid_bounds.longest_axis();
+        items.select_nth_unstable_by(mid, |a, b| a.1.centroid().e[axis].total_cmp(&b.1.centroid().e[axis]));
+        return mid
+    };
+
+    let extent = *centroid_bounds.axis_interval(axis);
+    let mut boundary = 0;
+    for i in 0..items.len() {
+        if bucket_of(&items[i].1, axis, extent) <= split {
+            items.swap(i, boundary);
+            boundary += 1;
+        }
+    }
+    boundary
+}
+
+fn bucket_of(b: &LightBounds, axis: usize, extent: Interval) -> usize {
+    let offset = (b.centroid().e[axis] - extent.min) / extent.size();
+    ((offset * SAOH_BUCKETS as f64) as usize).min(SAOH_BUCKETS - 1)
+}
+
+// Power times the solid angle the bounds' cones cover times surface area.
+fn orientation_cost(b: &LightBounds) -> f64 {
+    let theta_o = b.cos_theta_o.clamp(-1.0, 1.0).acos();
+    let theta_e = b.cos_theta_e.clamp(-1.0, 1.0).acos();
+    let theta_w = (theta_o + theta_e).min(PI);
+    let sin_theta_o = (1.0 - b.cos_theta_o * b.cos_theta_o).max(0.0).sqrt();
+    let m_omega = 2.0 * PI * (1.0 - b.cos_theta_o)
+        + PI / 2.0 * (2.0 * theta_w * sin_theta_o - (theta_o - 2.0 * theta_w).cos() - 2.0 * theta_o * sin_theta_o + b.cos_theta_o);
+    b.phi * m_omega * b.bounds.surface_area()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::PointLight;
+    use crate::seed_thread_rng;
+
+    // Point lights of different power scattered over a few units, so the
+    // tree has several levels and uneven importance.
+    fn test_lights() -> LightList {
+        let mut list = LightList::new();
+        for k in 0..11 {
+            let position = Point3::new((k % 4) as f64 * 1.5, (k / 4) as f64 * 2.0 - 1.0, (k * 7 % 5) as f64 - 2.0);
+            list.add(Arc::new(PointLight::new(position, Vec3::new(1.0, 1.0, 1.0), 1.0 + k as f64 * 3.0)));
+        }
+        list
+    }
+
+    // The chance the tree walk from `origin` ends at each leaf.
+    fn leaf_probabilities(bvh: &LightBvh, node: usize, pmf: f64, origin: Point3, out: &mut Vec<f64>) {
+        match &bvh.nodes[node] {
+            LightNode::Leaf { light, .. } => out[*light] += pmf,
+            LightNode::Interior { children, .. } => {
+                let importance = children.map(|child| bvh.nodes[child].bounds().importance(origin));
+                let total = importance[0] + importance[1];
+                for (child, weight) in children.iter().zip(importance) {
+                    leaf_probabilities(bvh, *child, pmf * weight / total, origin, out);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tree_probabilities_sum_to_one() {
+        seed_thread_rng(3);
+        let bvh = LightBvh::new(test_lights());
+        let root = bvh.root.unwrap();
+        for origin in [Point3::new(0.0, 0.0, 0.0), Point3::new(10.0, 3.0, -4.0), Point3::new(2.0, -5.0, 1.0)] {
+            let mut probabilities = vec![0.0; bvh.lights.len()];
+            leaf_probabilities(&bvh, root, 1.0, origin, &mut probabilities);
+            let total: f64 = probabilities.iter().sum();
+            assert!((total - 1.0).abs() < 1e-9, "total {}", total);
+
+            // The walk reports the same probability it chooses with.
+            let n = 100_000;
+            let mut counts = vec![0usize; bvh.lights.len()];
+            for _ in 0..n {
+                let (light, pmf) = bvh.sample_tree(origin).unwrap();
+                assert!((pmf - probabilities[light]).abs() < 1e-9);
+                counts[light] += 1;
+            }
+            for (count, p) in counts.iter().zip(&probabilities) {
+                assert!((*count as f64 / n as f64 - p).abs() < 0.01, "frequency {} for probability {}", *count as f64 / n as f64, p);
+            }
+        }
+    }
+
+    #[test]
+    fn power_probabilities_sum_to_one() {
+        let sampler = PowerLightSampler::new(test_lights());
+        let total: f64 = (0..sampler.lights.len()).map(|index| sampler.power.probability(index)).sum();
+        assert!((total - 1.0).abs() < 1e-9, "total {}", total);
+        assert!(sampler.power.probability(10) > sampler.power.probability(0));
+    }
+}
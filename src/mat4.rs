@@ -0,0 +1,175 @@
+use crate::degrees_to_radians;
+use crate::vec3::{Point3, Vec3};
+
+// Row-major 4x4 affine matrix acting on column vectors. Points carry an
+// implicit w = 1 and vectors w = 0; the bottom row is expected to stay
+// (0, 0, 0, 1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4{
+    pub m : [[f64; 4]; 4],
+}
+
+impl Mat4{
+    pub const IDENTITY: Mat4 = Mat4::new([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub const fn new(m: [[f64; 4]; 4]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        let mut t = Mat4::IDENTITY;
+        for i in 0..3 {
+            t.m[i][3] = offset.e[i];
+        }
+        t
+    }
+
+    pub fn scaling(factor: Vec3) -> Mat4 {
+        let mut s = Mat4::IDENTITY;
+        for i in 0..3 {
+            s.m[i][i] = factor.e[i];
+        }
+        s
+    }
+
+    // Right-handed rotation by `angle` degrees about the x (0), y (1) or
+    // z (2) axis.
+    pub fn rotation(axis: usize, angle: f64) -> Mat4 {
+        let radians = degrees_to_radians(angle);
+        let (sin_theta, cos_theta) = radians.sin_cos();
+        let (i, j) = ((axis + 1) % 3, (axis + 2) % 3);
+
+        let mut r = Mat4::IDENTITY;
+        r.m[i][i] = cos_theta;
+        r.m[i][j] = -sin_theta;
+        r.m[j][i] = sin_theta;
+        r.m[j][j] = cos_theta;
+        r
+    }
+
+    pub fn mul(a: &Mat4, b: &Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| a.m[i][k] * b.m[k][j]).sum();
+            }
+        }
+        Mat4::new(m)
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4::new(m)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting. Returns None for a
+    // singular matrix.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::IDENTITY.m;
+
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&r1, &r2| a[r1][col].abs().total_cmp(&a[r2][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for k in 0..4 {
+                a[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue
+                }
+                let factor = a[row][col];
+                for k in 0..4 {
+                    a[row][k] -= factor * a[col][k];
+                    inv[row][k] -= factor * inv[col][k];
+                }
+            }
+        }
+
+        Some(Mat4::new(inv))
+    }
+
+    // Determinant of the upper-left 3x3 block, the linear part of the map.
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        Point3::new(
+            m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+            m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+            m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3],
+        )
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(m: &Mat4) {
+        for (i, row) in m.m.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-12, "m[{}][{}] = {}", i, j, value);
+            }
+        }
+    }
+
+    #[test]
+    fn matrix_times_inverse_is_identity() {
+        let mut m = Mat4::scaling(Vec3::new(2.0, -0.5, 3.0));
+        m = Mat4::mul(&Mat4::rotation(0, 30.0), &m);
+        m = Mat4::mul(&Mat4::rotation(1, -75.0), &m);
+        m = Mat4::mul(&Mat4::translation(Vec3::new(1.0, -2.0, 5.0)), &m);
+        // A shear, so the matrix isn't just scale times rotation.
+        m.m[0][1] += 0.8;
+
+        let inverse = m.inverse().unwrap();
+        assert_identity(&Mat4::mul(&m, &inverse));
+        assert_identity(&Mat4::mul(&inverse, &m));
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        let m = Mat4::scaling(Vec3::new(1.0, 0.0, 1.0));
+        assert!(m.inverse().is_none());
+    }
+}
@@ -1,69 +1,127 @@
-use crate::{hittable::HitRecord, random_double, ray::Ray, vec3::Vec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
 
-pub struct EmptyMaterial;
+use crate::{hittable::HitRecord, pdf::{CosinePdf, Pdf, SpherePdf}, random_double, ray::Ray, texture::{SolidColor, Texture}, vec3::Vec3};
+
+// How a scattered ray is chosen.
+pub enum ScatterDirection {
+    // A single ray picked by the material itself, such as a mirror
+    // reflection. It has no usable density, so light sampling is skipped.
+    Specular(Ray),
+    // The caller draws the direction from this distribution, possibly mixed
+    // with light sampling.
+    Sampled(Box<dyn Pdf>),
+}
 
-impl Material for EmptyMaterial {
-    fn scatter(
-        &self,
-        _r_in: &Ray,
-        _rec: &HitRecord,
-        _attenuation: &mut Vec3,
-        _scattered: &mut Ray,
-    ) -> bool {
-        false
+pub struct ScatterRecord {
+    pub attenuation: Vec3,
+    pub direction: ScatterDirection,
+}
+
+impl ScatterRecord {
+    pub fn is_specular(&self) -> bool {
+        matches!(self.direction, ScatterDirection::Specular(_))
     }
 }
+
+pub struct EmptyMaterial;
+
+impl Material for EmptyMaterial {}
+
 pub trait Material: Send + Sync {
-    fn scatter(&self,_r_in: &Ray,_rec: &HitRecord,_attenuation: &mut Vec3,_scattered: &mut Ray) -> bool {
-        false
+    // None if the ray is absorbed.
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    // A scalar ratio, not a colour: BSDF × cosine for `scattered` over the
+    // attenuation `scatter` returned. For a colour times a fixed lobe, as in
+    // `Lambertian`, that is the lobe's density, hence the name. Only sampled
+    // (non-specular) scattering needs it.
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    // Solid-angle density of `scatter` choosing `scattered`, without
+    // drawing a sample. The same as `scattering_pdf` when the lobe is
+    // sampled exactly; materials that sample something else override it.
+    fn sampling_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    // BSDF × cosine for `scattered` as a colour, by default the attenuation
+    // scaled by `scattering_pdf`. Materials whose colour changes with
+    // direction override this instead.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, scattered: &Ray) -> Vec3 {
+        srec.attenuation.scalar_mul(self.scattering_pdf(r_in, rec, scattered))
     }
 }
 
 pub struct Lambertian{
-    pub albedo: Vec3,
+    pub tex: Arc<dyn Texture>,
 }
 
+// Mirror reflection perturbed by `fuzz`. Even when fuzzy it scatters as
+// `Specular`, since the perturbed lobe has no density to evaluate, so light
+// sampling and MIS never apply to it; use `Pbr` for rough metal lit by small
+// lights.
 pub struct Metal{
-    pub albedo: Vec3,
+    pub tex: Arc<dyn Texture>,
     pub fuzz: f64
 }
 
 impl Lambertian {
     pub fn new(albedo: Vec3) -> Self {
-        Self { albedo }
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
     }
 }
 
 impl Metal {
     pub fn new(albedo: Vec3, fuzz: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self { 
-            albedo: albedo,
+            tex,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
         }
     }
 }
 
 impl Material for Lambertian{
-    fn scatter(&self,_r_in: &Ray,rec: &HitRecord,attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
-        let mut scatter_direction = Vec3::add(rec.normal,Vec3::random_unit_vector());
-
-        if scatter_direction.near_zero(){
-            scatter_direction = rec.normal
-        }
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.tex.value(rec.u, rec.v, rec.p),
+            direction: ScatterDirection::Sampled(Box::new(CosinePdf::new(rec.normal))),
+        })
+    }
 
-        *scattered = Ray::new(rec.p, scatter_direction);
-        *attenuation = self.albedo;
-        return true
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = Vec3::dot_product(rec.normal, scattered.direction().unit_vector());
+        if cos_theta < 0.0 { 0.0 } else { cos_theta / PI }
     }
 }
 
 impl Material for Metal{
-    fn scatter(&self,r_in: &Ray,rec: &HitRecord,attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let mut reflected = Vec3::reflect(r_in.direction(), rec.normal);
-        reflected = Vec3::add(reflected, Vec3::random_unit_vector().scalar_mul(self.fuzz));
-        *scattered = Ray::new(rec.p, reflected);
-        *attenuation = self.albedo;
-        return Vec3::dot_product(scattered.direction(), rec.normal) > 0.0
+        reflected = reflected + Vec3::random_unit_vector().scalar_mul(self.fuzz);
+        if Vec3::dot_product(reflected, rec.normal) <= 0.0 {
+            return None
+        }
+        Some(ScatterRecord {
+            attenuation: self.tex.value(rec.u, rec.v, rec.p),
+            direction: ScatterDirection::Specular(Ray::with_time(rec.p, reflected, r_in.time())),
+        })
     }
 }
 
@@ -81,25 +139,82 @@ impl Dielectric{
     pub fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
         let mut r0 = (1.0 - refraction_index)/(1.0 + refraction_index);
         r0 = r0 * r0;
-        return r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
+        r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
     }
 }
 
 impl Material for Dielectric{
-    fn scatter(&self,r_in: &Ray,rec: &HitRecord,attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
-        *attenuation = Vec3::new(1.0,1.0,1.0);
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let ri = if rec.front_face {1.0/self.refraction_index} else { self.refraction_index };
         let unit_direction = (r_in.direction()).unit_vector();
         let cos_theta = Vec3::dot_product(unit_direction.scalar_mul(-1.0), rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let cannot_refract  = ri * sin_theta > 1.0;
-        let direction: Vec3;
-        if cannot_refract || (Dielectric::reflectance(cos_theta, ri) > random_double()){
-            direction = Vec3::reflect(unit_direction, rec.normal);
+        let direction = if cannot_refract || (Dielectric::reflectance(cos_theta, ri) > random_double()){
+            Vec3::reflect(unit_direction, rec.normal)
         }else{
-            direction = Vec3::refract(unit_direction, rec.normal, ri);
+            Vec3::refract(unit_direction, rec.normal, ri)
+        };
+        Some(ScatterRecord {
+            attenuation: Vec3::new(1.0,1.0,1.0),
+            direction: ScatterDirection::Specular(Ray::with_time(rec.p, direction, r_in.time())),
+        })
+    }
+}
+pub struct DiffuseLight{
+    pub tex: Arc<dyn Texture>,
+    // One-sided lights emit only from the side the surface normal points to.
+    pub two_sided: bool,
+}
+
+impl DiffuseLight{
+    pub fn new(emit: Vec3) -> DiffuseLight {
+        DiffuseLight::from_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>) -> DiffuseLight {
+        DiffuseLight { tex, two_sided: true }
+    }
+
+    pub fn one_sided(emit: Vec3) -> DiffuseLight {
+        DiffuseLight { tex: Arc::new(SolidColor::new(emit)), two_sided: false }
+    }
+}
+
+impl Material for DiffuseLight{
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        if !self.two_sided && !rec.front_face {
+            return Vec3::new(0.0, 0.0, 0.0)
         }
-        *scattered = Ray::new(rec.p, direction);
-        return true
+        self.tex.value(rec.u, rec.v, rec.p)
+    }
+}
+
+// Phase function for participating media: scatters uniformly in all
+// directions.
+pub struct Isotropic{
+    pub tex: Arc<dyn Texture>,
+}
+
+impl Isotropic{
+    pub fn new(albedo: Vec3) -> Isotropic {
+        Isotropic::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Isotropic {
+        Isotropic { tex }
     }
-}
\ No newline at end of file
+}
+
+impl Material for Isotropic{
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.tex.value(rec.u, rec.v, rec.p),
+            direction: ScatterDirection::Sampled(Box::new(SpherePdf)),
+        })
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::triangle::{intersect_triangle, triangle_bbox};
+use crate::vec3::{Point3, Vec3};
+
+// One face of a mesh. Each corner indexes the shared position buffer and,
+// optionally, the normal and texture coordinate buffers.
+#[derive(Clone, Copy, Debug)]
+pub struct MeshFace{
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+pub struct MeshData{
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<MeshFace>,
+}
+
+pub struct TriangleMesh{
+    pub data: Arc<MeshData>,
+    pub mat: Arc<dyn Material>,
+    bvh: BvhNode,
+}
+
+impl TriangleMesh{
+    pub fn new(data: MeshData, mat: Arc<dyn Material>) -> TriangleMesh {
+        let data = Arc::new(data);
+        let mut triangles: Vec<Arc<dyn Hittable>> = (0..data.faces.len())
+            .map(|face| Arc::new(MeshTriangle::new(data.clone(), face, mat.clone())) as Arc<dyn Hittable>)
+            .collect();
+
+        TriangleMesh {
+            bvh: BvhNode::from_objects(&mut triangles),
+            data,
+            mat,
+        }
+    }
+}
+
+impl Hittable for TriangleMesh{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        self.bvh.hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+struct MeshTriangle{
+    data: Arc<MeshData>,
+    face: usize,
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl MeshTriangle{
+    fn new(data: Arc<MeshData>, face: usize, mat: Arc<dyn Material>) -> MeshTriangle {
+        let [a, b, c] = data.faces[face].vertices.map(|i| data.positions[i]);
+        MeshTriangle { bbox: triangle_bbox(a, b, c), data, face, mat }
+    }
+}
+
+impl Hittable for MeshTriangle{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let face = &self.data.faces[self.face];
+        let [a, b, c] = face.vertices.map(|i| self.data.positions[i]);
+
+        let Some((t, b1, b2)) = intersect_triangle(a, b, c, r, ray_t) else {
+            return false
+        };
+        let b0 = 1.0 - b1 - b2;
+
+        rec.t = t;
+        rec.p = r.ray_at(t);
+
+        let geometric_normal = Vec3::cross_product(b - a, c - a).unit_vector();
+        rec.set_face_normal(r, geometric_normal);
+
+        // Smooth shading: interpolate the vertex normals, then keep the result
+        // on the same side of the surface as the geometric normal.
+        if let Some(indices) = face.normals {
+            let [n0, n1, n2] = indices.map(|i| self.data.normals[i]);
+            let shading_normal = n0.scalar_mul(b0) + n1.scalar_mul(b1) + n2.scalar_mul(b2);
+            if !shading_normal.near_zero() {
+                let shading_normal = shading_normal.unit_vector();
+                rec.normal = if Vec3::dot_product(shading_normal, rec.normal) < 0.0 {
+                    shading_normal.scalar_mul(-1.0)
+                } else {
+                    shading_normal
+                };
+            }
+        }
+
+        (rec.u, rec.v) = match face.uvs {
+            Some(indices) => {
+                let [t0, t1, t2] = indices.map(|i| self.data.uvs[i]);
+                (
+                    b0 * t0.0 + b1 * t1.0 + b2 * t2.0,
+                    b0 * t0.1 + b1 * t1.1 + b2 * t2.1,
+                )
+            }
+            None => (b1, b2),
+        };
+
+        rec.mat = self.mat.clone();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+
+    // One triangle in z = 0 with a different normal and uv at each corner.
+    fn test_mesh() -> TriangleMesh {
+        let data = MeshData {
+            positions: vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)],
+            normals: vec![Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 1.0)],
+            uvs: vec![(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)],
+            faces: vec![MeshFace { vertices: [0, 1, 2], normals: Some([0, 1, 2]), uvs: Some([0, 1, 2]) }],
+        };
+        TriangleMesh::new(data, Arc::new(EmptyMaterial))
+    }
+
+    #[test]
+    fn interpolates_normals_and_uvs() {
+        let mesh = test_mesh();
+        let mut rec = HitRecord::new();
+        // Barycentric weights (0.5, 0.25, 0.25).
+        let r = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+
+        let expected = Vec3::new(0.25, 0.25, 1.0).unit_vector();
+        assert!((rec.normal - expected).near_zero());
+        assert!((rec.u - 0.375).abs() < 1e-12 && (rec.v - 0.25).abs() < 1e-12);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn back_face_flips_shading_normal() {
+        let mesh = test_mesh();
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(0.25, 0.25, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(mesh.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!(!rec.front_face);
+        assert!(Vec3::dot_product(rec.normal, r.direction()) < 0.0);
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::mesh::{MeshData, MeshFace, TriangleMesh};
+use crate::texture::{Filter, ImageTexture, Texture, WrapMode};
+use crate::vec3::{Point3, Vec3};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, source: std::io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+// A loaded OBJ file, with problems that didn't stop the load for the
+// caller to report.
+pub struct ObjModel {
+    pub meshes: HittableList,
+    pub warnings: Vec<String>,
+}
+
+// Loads a Wavefront OBJ file and any material libraries it references.
+// Faces are grouped into one `TriangleMesh` per (group, material) pair. A
+// material library that can't be read is only a warning; faces using its
+// materials get the default grey instead.
+pub fn load_obj(path: &Path) -> Result<ObjModel, ObjError> {
+    let source = read_file(path)?;
+    let default_material: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::new(0.8, 0.8, 0.8)));
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut group = String::new();
+    let mut material_name = String::new();
+    let mut batches: Vec<FaceBatch> = Vec::new();
+    let mut batch_index: HashMap<(String, String), usize> = HashMap::new();
+    let mut missing_library = false;
+    let mut warnings = Vec::new();
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let mut line = LineParser::new(path, index + 1, raw_line);
+        let Some(keyword) = line.next_token() else { continue };
+
+        match keyword {
+            "v" => positions.push(line.parse_vec3()?),
+            "vn" => normals.push(line.parse_vec3()?),
+            "vt" => {
+                let u = line.parse_f64()?;
+                let v = line.parse_optional_f64()?.unwrap_or(0.0);
+                uvs.push((u, v));
+            }
+            "f" => {
+                let corners = line
+                    .remaining()
+                    .map(|token| line.parse_corner(token, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if corners.len() < 3 {
+                    return Err(line.error("face needs at least three vertices"))
+                }
+
+                let batch = *batch_index.entry((group.clone(), material_name.clone())).or_insert_with(|| {
+                    batches.push(FaceBatch { material: material_name.clone(), corners: Vec::new() });
+                    batches.len() - 1
+                });
+
+                // Fan triangulation; fine for the convex polygons exporters write.
+                for i in 1..corners.len() - 1 {
+                    batches[batch].corners.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "g" | "o" => group = line.remaining().collect::<Vec<_>>().join(" "),
+            "usemtl" => {
+                material_name = line.expect_token("material name")?.to_string();
+                if !materials.contains_key(&material_name) && !missing_library {
+                    return Err(line.error(&format!("unknown material '{}'", material_name)))
+                }
+            }
+            "mtllib" => {
+                let directory = path.parent().unwrap_or(Path::new(""));
+                for library in line.remaining() {
+                    match load_mtl(&directory.join(library)) {
+                        Ok(library) => materials.extend(library),
+                        Err(err @ ObjError::Io { .. }) => {
+                            warnings.push(format!("{}; using the default material", err));
+                            missing_library = true;
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut meshes = HittableList::new();
+    for batch in batches {
+        let mat = materials.get(&batch.material).unwrap_or(&default_material).clone();
+        let data = batch.into_mesh_data(&positions, &normals, &uvs);
+        meshes.add(Arc::new(TriangleMesh::new(data, mat)));
+    }
+    Ok(ObjModel { meshes, warnings })
+}
+
+// Parses a material library, approximating each entry with the closest
+// built-in material: transparent entries become `Dielectric`, entries with a
+// mirror illumination model (or only a specular colour) become `Metal`, and
+// everything else is `Lambertian`, textured when `map_Kd` is given.
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let source = read_file(path)?;
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlEntry)> = None;
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let mut line = LineParser::new(path, index + 1, raw_line);
+        let Some(keyword) = line.next_token() else { continue };
+
+        if keyword == "newmtl" {
+            let name = line.expect_token("material name")?.to_string();
+            if let Some((name, entry)) = current.replace((name, MtlEntry::default())) {
+                materials.insert(name, entry.into_material());
+            }
+            continue
+        }
+
+        let Some((_, entry)) = current.as_mut() else {
+            if matches!(keyword, "Kd" | "Ks" | "Ns" | "Ni" | "d" | "Tr" | "illum" | "map_Kd") {
+                return Err(line.error(&format!("'{}' before any newmtl", keyword)))
+            }
+            continue
+        };
+
+        match keyword {
+            "Kd" => entry.diffuse = line.parse_color()?,
+            "Ks" => entry.specular = line.parse_color()?,
+            "Ns" => entry.shininess = line.parse_f64()?,
+            "Ni" => entry.refraction_index = line.parse_f64()?,
+            "d" => entry.dissolve = line.parse_f64()?,
+            "Tr" => entry.dissolve = 1.0 - line.parse_f64()?,
+            "map_Kd" => {
+                // Texture options such as `-s 1 1 1` precede the file name.
+                let file = line.remaining().last().ok_or_else(|| line.error("missing texture file"))?;
+                let texture_path = path.parent().unwrap_or(Path::new("")).join(file);
+                let texture = ImageTexture::open(&texture_path, WrapMode::Repeat, Filter::Bilinear)
+                    .map_err(|err| line.error(&format!("cannot load texture '{}': {}", texture_path.display(), err)))?;
+                entry.diffuse_texture = Some(Arc::new(texture));
+            }
+            "illum" => {
+                let token = line.expect_token("illumination model")?;
+                entry.illum = token.parse().map_err(|_| line.error(&format!("invalid illumination model '{}'", token)))?;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((name, entry)) = current {
+        materials.insert(name, entry.into_material());
+    }
+    Ok(materials)
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io { path: path.to_path_buf(), source })
+}
+
+#[derive(Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct FaceBatch {
+    material: String,
+    corners: Vec<[Corner; 3]>,
+}
+
+impl FaceBatch {
+    // Copies the vertices referenced by this batch into a compact mesh. A
+    // face only gets normals/uvs when all three of its corners supply them.
+    fn into_mesh_data(self, positions: &[Point3], normals: &[Vec3], uvs: &[(f64, f64)]) -> MeshData {
+        let mut data = MeshData { positions: Vec::new(), normals: Vec::new(), uvs: Vec::new(), faces: Vec::new() };
+        let mut position_map = HashMap::new();
+        let mut normal_map = HashMap::new();
+        let mut uv_map = HashMap::new();
+
+        for triangle in &self.corners {
+            let mut face = MeshFace { vertices: [0; 3], normals: Some([0; 3]), uvs: Some([0; 3]) };
+            for (k, corner) in triangle.iter().enumerate() {
+                face.vertices[k] = remap(corner.position, positions, &mut position_map, &mut data.positions);
+
+                face.normals = match (face.normals, corner.normal) {
+                    (Some(mut n), Some(index)) => {
+                        n[k] = remap(index, normals, &mut normal_map, &mut data.normals);
+                        Some(n)
+                    }
+                    _ => None,
+                };
+                face.uvs = match (face.uvs, corner.uv) {
+                    (Some(mut t), Some(index)) => {
+                        t[k] = remap(index, uvs, &mut uv_map, &mut data.uvs);
+                        Some(t)
+                    }
+                    _ => None,
+                };
+            }
+            data.faces.push(face);
+        }
+        data
+    }
+}
+
+fn remap<T: Copy>(index: usize, source: &[T], map: &mut HashMap<usize, usize>, target: &mut Vec<T>) -> usize {
+    *map.entry(index).or_insert_with(|| {
+        target.push(source[index]);
+        target.len() - 1
+    })
+}
+
+struct MtlEntry {
+    diffuse: Vec3,
+    diffuse_texture: Option<Arc<dyn Texture>>,
+    specular: Vec3,
+    shininess: f64,
+    refraction_index: f64,
+    dissolve: f64,
+    illum: u32,
+}
+
+impl Default for MtlEntry {
+    fn default() -> Self {
+        MtlEntry {
+            diffuse: Vec3::new(0.8, 0.8, 0.8),
+            diffuse_texture: None,
+            specular: Vec3::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            refraction_index: 1.0,
+            dissolve: 1.0,
+            illum: 1,
+        }
+    }
+}
+
+impl MtlEntry {
+    fn into_material(self) -> Arc<dyn Material> {
+        let transparent = self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9);
+        let mirror = matches!(self.illum, 3 | 5 | 8);
+        let has_specular = !self.specular.near_zero();
+
+        if transparent {
+            let ri = if self.refraction_index > 1.0 { self.refraction_index } else { 1.5 };
+            Arc::new(Dielectric::new(ri))
+        } else if mirror || (has_specular && self.illum == 2 && self.diffuse.near_zero()) {
+            // Map the Phong exponent onto a roughness-like fuzz amount.
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            let albedo = if has_specular { self.specular } else { self.diffuse };
+            Arc::new(Metal::new(albedo, fuzz))
+        } else if let Some(texture) = self.diffuse_texture {
+            Arc::new(Lambertian::from_texture(texture))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+struct LineParser<'a> {
+    path: &'a Path,
+    line: usize,
+    tokens: std::str::SplitWhitespace<'a>,
+}
+
+impl<'a> LineParser<'a> {
+    fn new(path: &'a Path, line: usize, text: &'a str) -> Self {
+        let text = text.split('#').next().unwrap_or("");
+        LineParser { path, line, tokens: text.split_whitespace() }
+    }
+
+    fn error(&self, message: &str) -> ObjError {
+        ObjError::Parse { path: self.path.to_path_buf(), line: self.line, message: message.to_string() }
+    }
+
+    fn next_token(&mut self) -> Option<&'a str> {
+        self.tokens.next()
+    }
+
+    fn remaining(&mut self) -> impl Iterator<Item = &'a str> + use<'a> {
+        std::mem::replace(&mut self.tokens, "".split_whitespace())
+    }
+
+    fn expect_token(&mut self, what: &str) -> Result<&'a str, ObjError> {
+        self.tokens.next().ok_or_else(|| self.error(&format!("missing {}", what)))
+    }
+
+    fn parse_f64(&mut self) -> Result<f64, ObjError> {
+        let token = self.expect_token("number")?;
+        token.parse().map_err(|_| self.error(&format!("invalid number '{}'", token)))
+    }
+
+    fn parse_optional_f64(&mut self) -> Result<Option<f64>, ObjError> {
+        match self.tokens.next() {
+            Some(token) => token.parse().map(Some).map_err(|_| self.error(&format!("invalid number '{}'", token))),
+            None => Ok(None),
+        }
+    }
+
+    fn parse_vec3(&mut self) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(self.parse_f64()?, self.parse_f64()?, self.parse_f64()?))
+    }
+
+    // A single value is shorthand for a grey.
+    fn parse_color(&mut self) -> Result<Vec3, ObjError> {
+        let r = self.parse_f64()?;
+        match self.parse_optional_f64()? {
+            Some(g) => Ok(Vec3::new(r, g, self.parse_f64()?)),
+            None => Ok(Vec3::new(r, r, r)),
+        }
+    }
+
+    // Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolving negative indices
+    // against the number of elements defined so far. Faces may only refer to
+    // elements that appear earlier in the file.
+    fn parse_corner(&self, token: &str, positions: usize, uvs: usize, normals: usize) -> Result<Corner, ObjError> {
+        let mut parts = token.split('/');
+        let position = self.resolve_index(parts.next(), positions, token)?
+            .ok_or_else(|| self.error(&format!("missing vertex index in '{}'", token)))?;
+        let uv = self.resolve_index(parts.next(), uvs, token)?;
+        let normal = self.resolve_index(parts.next(), normals, token)?;
+        if parts.next().is_some() {
+            return Err(self.error(&format!("malformed face vertex '{}'", token)))
+        }
+        Ok(Corner { position, uv, normal })
+    }
+
+    fn resolve_index(&self, part: Option<&str>, count: usize, token: &str) -> Result<Option<usize>, ObjError> {
+        let Some(part) = part.filter(|p| !p.is_empty()) else { return Ok(None) };
+        let index: i64 = part.parse().map_err(|_| self.error(&format!("invalid index in '{}'", token)))?;
+        let resolved = match index {
+            0 => return Err(self.error(&format!("index 0 in '{}' (OBJ indices start at 1)", token))),
+            i if i > 0 => i - 1,
+            i => count as i64 + i,
+        };
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(self.error(&format!("index {} in '{}' is out of range ({} defined)", index, token, count)))
+        }
+        Ok(Some(resolved as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::HitRecord;
+    use crate::interval::Interval;
+    use crate::ray::Ray;
+
+    // Writes `files` into a fresh directory under the system temp dir and
+    // returns the path of the first one.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raytracer-obj-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir.join(files[0].0)
+    }
+
+    fn hits(list: &HittableList, x: f64, y: f64) -> bool {
+        let r = Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::new();
+        list.objects.iter().any(|object| object.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec))
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let path = write_files("negative", &[("model.obj", "\
+v 5 5 0
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f -3 -2 -1
+")]);
+        let list = load_obj(&path).unwrap().meshes;
+        assert!(hits(&list, 0.2, 0.2));
+        assert!(!hits(&list, 0.8, 0.8));
+    }
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let path = write_files("ngon", &[("model.obj", "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1 2 3 4
+")]);
+        let list = load_obj(&path).unwrap().meshes;
+        assert!(hits(&list, 0.8, 0.2));
+        assert!(hits(&list, 0.2, 0.8));
+        assert!(!hits(&list, 1.2, 0.5));
+    }
+
+    #[test]
+    fn errors_report_the_line_number() {
+        let path = write_files("errors", &[("model.obj", "\
+# a comment
+v 0 0 0
+v 1 0 0
+f 1 2 7
+")]);
+        match load_obj(&path) {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, 4),
+            other => panic!("expected a parse error, got {:?}", other.err()),
+        }
+
+        let path = write_files("errors-mtl", &[("lib.mtl", "newmtl a\nKd 1 x 0\n")]);
+        match load_mtl(&path) {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, 2),
+            other => panic!("expected a parse error, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn missing_material_library_falls_back_to_the_default() {
+        let path = write_files("missing-mtl", &[("model.obj", "\
+mtllib nowhere.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl red
+f 1 2 3
+")]);
+        let model = load_obj(&path).unwrap();
+        assert!(hits(&model.meshes, 0.2, 0.2));
+        assert_eq!(model.warnings.len(), 1);
+        assert!(model.warnings[0].contains("nowhere.mtl"), "{}", model.warnings[0]);
+    }
+}
@@ -0,0 +1,25 @@
+use crate::vec3::Vec3;
+
+// Orthonormal basis with `w` along a given direction, for building local
+// frames around normals and light directions.
+#[derive(Clone, Copy, Debug)]
+pub struct Onb{
+    pub u : Vec3,
+    pub v : Vec3,
+    pub w : Vec3,
+}
+
+impl Onb{
+    pub fn new(n: Vec3) -> Onb {
+        let w = n.unit_vector();
+        let a = if w.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = Vec3::cross_product(w, a).unit_vector();
+        let u = Vec3::cross_product(w, v);
+        Onb { u, v, w }
+    }
+
+    // Local coordinates to world space.
+    pub fn transform(&self, v: Vec3) -> Vec3 {
+        self.u.scalar_mul(v.x()) + self.v.scalar_mul(v.y()) + self.w.scalar_mul(v.z())
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::{self, Write};
+
+use crate::color::{color_to_u16, color_to_u8, write_color};
+use crate::image::Image;
+use crate::vec3::Vec3;
+
+pub fn write_ppm<W: Write>(out: &mut W, image: &Image) -> io::Result<()> {
+    writeln!(out, "P3\n{} {}\n255", image.width, image.height)?;
+    for pixel_color in &image.pixels {
+        write_color(out, *pixel_color)?;
+    }
+    out.flush()
+}
+
+// Encodes the image as an RGB PNG with 8 or 16 bits per channel, using the
+// same gamma and clamping as the PPM writer.
+pub fn write_png<W: Write>(out: &mut W, image: &Image, bit_depth: u8) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(out, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+
+    let data: Vec<u8> = match bit_depth {
+        8 => {
+            encoder.set_depth(png::BitDepth::Eight);
+            image.pixels.iter().flat_map(|p| color_to_u8(*p)).collect()
+        }
+        16 => {
+            encoder.set_depth(png::BitDepth::Sixteen);
+            image.pixels.iter().flat_map(|p| color_to_u16(*p)).flat_map(u16::to_be_bytes).collect()
+        }
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unsupported PNG bit depth {}", bit_depth))),
+    };
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&data).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExrPrecision {
+    Half,
+    Float,
+}
+
+// A named image written as one part of an EXR file.
+pub struct ExrLayer<'a> {
+    pub name: &'a str,
+    pub image: &'a Image,
+}
+
+// Writes unclipped linear radiance as OpenEXR. A single layer with an empty
+// name is written as a plain RGB image; otherwise every layer is stored as a
+// separate named part.
+pub fn write_exr<W: Write>(out: &mut W, layers: &[ExrLayer], precision: ExrPrecision) -> io::Result<()> {
+    use exr::prelude::*;
+
+    let mut exr_layers = Vec::new();
+    for layer in layers {
+        let size = Vec2(layer.image.width, layer.image.height);
+
+        let channel = |name: &str, c: usize| {
+            let values = layer.image.pixels.iter().map(|p| p.e[c] as f32);
+            let samples = match precision {
+                ExrPrecision::Half => FlatSamples::F16(values.map(f16::from_f32).collect()),
+                ExrPrecision::Float => FlatSamples::F32(values.collect()),
+            };
+            AnyChannel::new(name, samples)
+        };
+        let channels = AnyChannels::sort(vec![channel("R", 0), channel("G", 1), channel("B", 2)].into());
+
+        let attributes = if layer.name.is_empty() {
+            LayerAttributes::default()
+        } else {
+            LayerAttributes::named(layer.name)
+        };
+        exr_layers.push(Layer::new(size, attributes, Encoding::SMALL_LOSSLESS, channels));
+    }
+
+    let size = exr_layers.first().map_or(Vec2(0, 0), |layer| layer.size);
+    let exr_image = exr::prelude::Image::from_layers(ImageAttributes::new(IntegerBounds::from_dimensions(size)), exr_layers);
+
+    // The encoder needs to seek, which standard output cannot do.
+    let mut buffer = io::Cursor::new(Vec::new());
+    exr_image.write().to_buffered(&mut buffer).map_err(io::Error::other)?;
+    out.write_all(buffer.get_ref())?;
+    out.flush()
+}
+
+// Writes an uncompressed Radiance RGBE (.hdr) image.
+pub fn write_hdr<W: Write>(out: &mut W, image: &Image) -> io::Result<()> {
+    write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", image.height, image.width)?;
+    for pixel_color in &image.pixels {
+        out.write_all(&color_to_rgbe(*pixel_color))?;
+    }
+    out.flush()
+}
+
+fn color_to_rgbe(pixel_color: Vec3) -> [u8; 4] {
+    let [r, g, b] = pixel_color.e.map(|c| c.max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0]
+    }
+    // v = m * 2^e with m in [0.5, 1); the shared exponent is e + 128.
+    let e = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(e);
+    let byte = |c: f64| (c * scale).min(255.0) as u8;
+    [byte(r), byte(g), byte(b), (e + 128).clamp(0, 255) as u8]
+}
+
+// Writes a little-endian colour Portable Float Map. PFM stores rows bottom
+// to top.
+pub fn write_pfm<W: Write>(out: &mut W, image: &Image) -> io::Result<()> {
+    write!(out, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+    for row in image.rows().rev() {
+        for pixel_color in row {
+            for c in pixel_color.e {
+                out.write_all(&(c as f32).to_le_bytes())?;
+            }
+        }
+    }
+    out.flush()
+}
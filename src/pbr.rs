@@ -0,0 +1,350 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterDirection, ScatterRecord};
+use crate::onb::Onb;
+use crate::pdf::{random_cosine_direction, Pdf};
+use crate::random_double;
+use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::Vec3;
+
+// Metallic/roughness material in the glTF style: a GGX (Trowbridge-Reitz)
+// specular lobe with height-correlated Smith shadowing and Schlick Fresnel,
+// over a Lambertian base that fades out as `metallic` goes to 1. Metallic
+// and roughness are read from the red channel of their textures. With a
+// `conductor`, the metallic part uses its exact Fresnel instead of Schlick
+// tinted by the base colour.
+pub struct Pbr{
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    pub conductor: Option<Conductor>,
+}
+
+// A metal's complex index of refraction eta + ik, per RGB channel.
+#[derive(Clone, Copy, Debug)]
+pub struct Conductor {
+    pub eta: Vec3,
+    pub k: Vec3,
+}
+
+impl Conductor {
+    pub fn new(eta: Vec3, k: Vec3) -> Conductor {
+        Conductor { eta, k }
+    }
+
+    // Unpolarised reflectance at `cos_theta` from the normal.
+    fn fresnel(&self, cos_theta: f64) -> Vec3 {
+        let channel = |i: usize| conductor_fresnel(cos_theta, self.eta.e[i], self.k.e[i]);
+        Vec3::new(channel(0), channel(1), channel(2))
+    }
+}
+
+// Inputs looked up at one hit point.
+struct Inputs {
+    base_color: Vec3,
+    metallic: f64,
+    alpha: f64,
+}
+
+impl Pbr{
+    pub fn new(base_color: Vec3, metallic: f64, roughness: f64) -> Pbr {
+        let scalar = |x: f64| Arc::new(SolidColor::new(Vec3::new(x, x, x)));
+        Pbr::from_textures(Arc::new(SolidColor::new(base_color)), scalar(metallic), scalar(roughness))
+    }
+
+    pub fn from_textures(base_color: Arc<dyn Texture>, metallic: Arc<dyn Texture>, roughness: Arc<dyn Texture>) -> Pbr {
+        Pbr { base_color, metallic, roughness, conductor: None }
+    }
+
+    pub fn with_conductor(self, conductor: Conductor) -> Pbr {
+        Pbr { conductor: Some(conductor), ..self }
+    }
+
+    fn inputs(&self, rec: &HitRecord) -> Inputs {
+        let metallic = self.metallic.value(rec.u, rec.v, rec.p).x().clamp(0.0, 1.0);
+        let roughness = self.roughness.value(rec.u, rec.v, rec.p).x().clamp(0.0, 1.0);
+        Inputs {
+            base_color: self.base_color.value(rec.u, rec.v, rec.p),
+            metallic,
+            // Perceptual roughness squared, kept off zero so the lobe stays
+            // a distribution rather than a delta.
+            alpha: (roughness * roughness).max(1e-3),
+        }
+    }
+}
+
+// `scattering_pdf` keeps the zero default: Fresnel tints the reflection,
+// so BSDF × cosine isn't a scalar multiple of the attenuation. `eval` and
+// `sampling_pdf` cover both of its uses.
+impl Material for Pbr{
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let inputs = self.inputs(rec);
+        Some(ScatterRecord {
+            attenuation: inputs.base_color,
+            direction: ScatterDirection::Sampled(Box::new(GgxPdf::new(r_in, rec, &inputs)?)),
+        })
+    }
+
+    fn sampling_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        GgxPdf::new(r_in, rec, &self.inputs(rec)).map_or(0.0, |pdf| pdf.value(scattered.direction()))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, _srec: &ScatterRecord, scattered: &Ray) -> Vec3 {
+        let black = Vec3::new(0.0, 0.0, 0.0);
+        let inputs = self.inputs(rec);
+        let uvw = Onb::new(rec.normal);
+        let wo = to_local(&uvw, r_in.direction().scalar_mul(-1.0).unit_vector());
+        let wi = to_local(&uvw, scattered.direction().unit_vector());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return black
+        }
+
+        let h = (wo + wi).unit_vector();
+        let cos_theta = Vec3::dot_product(wo, h);
+        let dielectric = Vec3::new(0.04, 0.04, 0.04);
+        let fresnel = match &self.conductor {
+            Some(conductor) => lerp(schlick_fresnel(dielectric, cos_theta), conductor.fresnel(cos_theta), inputs.metallic),
+            None => schlick_fresnel(lerp(dielectric, inputs.base_color, inputs.metallic), cos_theta),
+        };
+
+        // Specular BSDF times cos(wi): F D G / (4 cos(wo)).
+        let d = ggx_d(h.z(), inputs.alpha);
+        let g = 1.0 / (1.0 + smith_lambda(wo.z(), inputs.alpha) + smith_lambda(wi.z(), inputs.alpha));
+        let specular = fresnel.scalar_mul(d * g / (4.0 * wo.z()));
+
+        // Light not reflected at the surface enters the diffuse base, and
+        // must get out through it again. Weighting by the Fresnel of the
+        // macro normal both ways keeps this reciprocal; the microfacet
+        // half-vector would let grazing light reflect almost fully off the
+        // lobe and still reach the base.
+        let diffuse_albedo = inputs.base_color.scalar_mul(1.0 - inputs.metallic);
+        let transmitted = |cos: f64| 1.0 - schlick_fresnel(dielectric, cos).x();
+        let diffuse = diffuse_albedo.scalar_mul(transmitted(wo.z()) * transmitted(wi.z()) * wi.z() / PI);
+
+        specular + diffuse
+    }
+}
+
+// Mixes visible-normal sampling of the GGX lobe with cosine sampling of the
+// diffuse base. Directions are kept in the local frame around the normal.
+struct GgxPdf{
+    uvw: Onb,
+    wo: Vec3,
+    alpha: f64,
+    specular_probability: f64,
+}
+
+impl GgxPdf{
+    // None when `r_in` arrives from below the surface.
+    fn new(r_in: &Ray, rec: &HitRecord, inputs: &Inputs) -> Option<GgxPdf> {
+        let uvw = Onb::new(rec.normal);
+        let wo = to_local(&uvw, r_in.direction().scalar_mul(-1.0).unit_vector());
+        if wo.z() <= 0.0 {
+            return None
+        }
+        Some(GgxPdf { uvw, wo, alpha: inputs.alpha, specular_probability: specular_probability(inputs.metallic) })
+    }
+}
+
+impl Pdf for GgxPdf{
+    fn value(&self, direction: Vec3) -> f64 {
+        let wi = to_local(&self.uvw, direction.unit_vector());
+        if wi.z() <= 0.0 {
+            return 0.0
+        }
+
+        // Density of the reflected direction under VNDF sampling:
+        // G1(wo) D(h) / (4 cos(wo)).
+        let h = (self.wo + wi).unit_vector();
+        let g1 = 1.0 / (1.0 + smith_lambda(self.wo.z(), self.alpha));
+        let specular = g1 * ggx_d(h.z(), self.alpha) / (4.0 * self.wo.z());
+        let diffuse = wi.z() / PI;
+
+        self.specular_probability * specular + (1.0 - self.specular_probability) * diffuse
+    }
+
+    fn generate(&self) -> Vec3 {
+        let local = if random_double() < self.specular_probability {
+            let h = sample_ggx_vndf(self.wo, self.alpha);
+            h.scalar_mul(2.0 * Vec3::dot_product(self.wo, h)) - self.wo
+        } else {
+            random_cosine_direction()
+        };
+        self.uvw.transform(local)
+    }
+}
+
+// Fully metallic surfaces have no diffuse lobe to sample.
+fn specular_probability(metallic: f64) -> f64 {
+    0.5 + 0.5 * metallic
+}
+
+fn to_local(uvw: &Onb, v: Vec3) -> Vec3 {
+    Vec3::new(Vec3::dot_product(v, uvw.u), Vec3::dot_product(v, uvw.v), Vec3::dot_product(v, uvw.w))
+}
+
+fn lerp(a: Vec3, b: Vec3, t: f64) -> Vec3 {
+    a.scalar_mul(1.0 - t) + b.scalar_mul(t)
+}
+
+fn schlick_fresnel(f0: Vec3, cos_theta: f64) -> Vec3 {
+    let weight = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 + (Vec3::new(1.0, 1.0, 1.0) - f0).scalar_mul(weight)
+}
+
+// Fresnel reflectance of a conductor with complex index eta + ik, averaged
+// over both polarisations (as in PBRT's FrConductor).
+fn conductor_fresnel(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos2.sqrt() * a;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rs + rp)
+}
+
+// GGX normal distribution for a microfacet normal at cos_theta from the
+// macro normal.
+fn ggx_d(cos_theta: f64, alpha: f64) -> f64 {
+    if cos_theta <= 0.0 {
+        return 0.0
+    }
+    let a2 = alpha * alpha;
+    let t = cos_theta * cos_theta * (a2 - 1.0) + 1.0;
+    a2 / (PI * t * t)
+}
+
+// Smith Lambda for GGX; G1 = 1 / (1 + Lambda).
+fn smith_lambda(cos_theta: f64, alpha: f64) -> f64 {
+    let cos2 = cos_theta * cos_theta;
+    let tan2 = (1.0 - cos2).max(0.0) / cos2;
+    0.5 * (-1.0 + (1.0 + alpha * alpha * tan2).sqrt())
+}
+
+// Samples a microfacet normal from the distribution of normals visible from
+// `wo` (Heitz 2018), in the local frame.
+fn sample_ggx_vndf(wo: Vec3, alpha: f64) -> Vec3 {
+    // Stretch the view direction to the hemisphere configuration.
+    let vh = Vec3::new(alpha * wo.x(), alpha * wo.y(), wo.z()).unit_vector();
+
+    let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+    let t1 = if lensq > 0.0 { Vec3::new(-vh.y(), vh.x(), 0.0).scalar_div(lensq.sqrt()) } else { Vec3::new(1.0, 0.0, 0.0) };
+    let t2 = Vec3::cross_product(vh, t1);
+
+    // Uniform point on a disk, warped towards the visible half.
+    let r = random_double().sqrt();
+    let phi = 2.0 * PI * random_double();
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z());
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+    let nh = t1.scalar_mul(p1) + t2.scalar_mul(p2) + vh.scalar_mul((1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt());
+
+    // Unstretch back to the ellipsoid configuration.
+    Vec3::new(alpha * nh.x(), alpha * nh.y(), nh.z().max(0.0)).unit_vector()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seed_thread_rng;
+    use crate::vec3::Point3;
+
+    // A hit on the z = 0 plane facing +z, seen from `theta` degrees off the
+    // normal.
+    fn view(theta: f64) -> (Ray, HitRecord) {
+        let (sin, cos) = theta.to_radians().sin_cos();
+        let r_in = Ray::new(Point3::new(sin, 0.0, cos), Vec3::new(-sin, 0.0, -cos));
+        let mut rec = HitRecord::new();
+        rec.normal = Vec3::new(0.0, 0.0, 1.0);
+        rec.front_face = true;
+        (r_in, rec)
+    }
+
+    // Fraction of light reflected towards `r_in`, by sampling the material.
+    fn albedo(mat: &Pbr, theta: f64) -> Vec3 {
+        let (r_in, rec) = view(theta);
+        let srec = mat.scatter(&r_in, &rec).unwrap();
+        let ScatterDirection::Sampled(pdf) = &srec.direction else { panic!("expected a sampled lobe") };
+        let n = 100_000;
+        let mut sum = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            let scattered = Ray::new(rec.p, pdf.generate());
+            let density = pdf.value(scattered.direction());
+            if density > 0.0 {
+                sum = sum + mat.eval(&r_in, &rec, &srec, &scattered).scalar_div(density);
+            }
+        }
+        sum.scalar_div(n as f64)
+    }
+
+    #[test]
+    fn white_furnace_does_not_gain_energy() {
+        seed_thread_rng(9);
+        let rough = Pbr::new(Vec3::new(1.0, 1.0, 1.0), 1.0, 1.0);
+        let smooth = Pbr::new(Vec3::new(1.0, 1.0, 1.0), 1.0, 0.1);
+        let plastic = Pbr::new(Vec3::new(1.0, 1.0, 1.0), 0.0, 0.5);
+        for theta in [0.0, 30.0, 60.0, 85.0] {
+            for mat in [&rough, &smooth, &plastic] {
+                let albedo = albedo(mat, theta);
+                assert!(albedo.e.iter().all(|a| *a <= 1.01), "albedo {:?} at {}°", albedo, theta);
+            }
+            // Little is lost to shadowing on a smooth mirror.
+            assert!(albedo(&smooth, theta).x() > 0.9);
+        }
+    }
+
+    // The lobe at `theta` degrees for `metallic` and `roughness`.
+    fn lobe(metallic: f64, roughness: f64, theta: f64) -> GgxPdf {
+        let (r_in, rec) = view(theta);
+        GgxPdf::new(&r_in, &rec, &Pbr::new(Vec3::new(1.0, 1.0, 1.0), metallic, roughness).inputs(&rec)).unwrap()
+    }
+
+    #[test]
+    fn vndf_pdf_matches_its_samples() {
+        seed_thread_rng(10);
+        let n = 200_000;
+        for (metallic, roughness, theta) in [(1.0, 0.5, 0.0), (1.0, 0.8, 70.0), (0.3, 0.6, 40.0)] {
+            let pdf = lobe(metallic, roughness, theta);
+
+            // The density integrates to the chance a sample stays above the
+            // surface; reflections below it are discarded.
+            let integral = (0..n).map(|_| pdf.value(Vec3::random_unit_vector())).sum::<f64>() / n as f64 * 4.0 * PI;
+            let mut above = 0;
+            // E[1/pdf] over samples above the surface is the hemisphere's
+            // solid angle.
+            let mut hemisphere = 0.0;
+            for _ in 0..n {
+                let direction = pdf.generate();
+                if direction.z() > 0.0 {
+                    above += 1;
+                    hemisphere += 1.0 / pdf.value(direction);
+                }
+            }
+            let above = above as f64 / n as f64;
+            let hemisphere = hemisphere / n as f64;
+            assert!((integral - above).abs() < 0.03, "integral {} but {} of samples above", integral, above);
+            assert!((hemisphere / (2.0 * PI) - 1.0).abs() < 0.05, "solid angle {}", hemisphere);
+        }
+    }
+
+    #[test]
+    fn conductor_fresnel_limits() {
+        // Normal incidence: ((eta - 1)² + k²) / ((eta + 1)² + k²).
+        let (eta, k) = (0.27, 3.61);
+        let expected = ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k);
+        assert!((conductor_fresnel(1.0, eta, k) - expected).abs() < 1e-12);
+        assert!((conductor_fresnel(0.0, eta, k) - 1.0).abs() < 1e-12);
+        // With no absorption it matches a dielectric at normal incidence.
+        assert!((conductor_fresnel(1.0, 1.5, 0.0) - 0.04).abs() < 1e-12);
+    }
+}
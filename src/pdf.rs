@@ -0,0 +1,108 @@
+use std::f64::consts::PI;
+
+use crate::hittable::Hittable;
+use crate::onb::Onb;
+use crate::random_double;
+use crate::vec3::{Point3, Vec3};
+
+// A distribution over directions, given as a solid-angle density.
+pub trait Pdf: Send + Sync {
+    fn value(&self, direction: Vec3) -> f64;
+
+    fn generate(&self) -> Vec3;
+}
+
+// Uniform over the whole sphere of directions.
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf{
+    fn value(&self, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        Vec3::random_unit_vector()
+    }
+}
+
+// Cosine-weighted over the hemisphere around a normal.
+pub struct CosinePdf{
+    uvw : Onb,
+}
+
+impl CosinePdf{
+    pub fn new(w: Vec3) -> CosinePdf {
+        CosinePdf { uvw: Onb::new(w) }
+    }
+}
+
+impl Pdf for CosinePdf{
+    fn value(&self, direction: Vec3) -> f64 {
+        let cosine_theta = Vec3::dot_product(direction.unit_vector(), self.uvw.w);
+        (cosine_theta / PI).max(0.0)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.uvw.transform(random_cosine_direction())
+    }
+}
+
+// Directions from `origin` towards points sampled on `objects`, using their
+// `Hittable::pdf_value` and `Hittable::random`.
+pub struct HittablePdf<'a>{
+    objects : &'a dyn Hittable,
+    origin : Point3,
+}
+
+impl<'a> HittablePdf<'a>{
+    pub fn new(objects: &'a dyn Hittable, origin: Point3) -> HittablePdf<'a> {
+        HittablePdf { objects, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_>{
+    fn value(&self, direction: Vec3) -> f64 {
+        self.objects.pdf_value(self.origin, direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.objects.random(self.origin)
+    }
+}
+
+// Even mix of two distributions.
+pub struct MixturePdf<'a>{
+    p : [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a>{
+    pub fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> MixturePdf<'a> {
+        MixturePdf { p: [p0, p1] }
+    }
+}
+
+impl Pdf for MixturePdf<'_>{
+    fn value(&self, direction: Vec3) -> f64 {
+        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        if random_double() < 0.5 {
+            self.p[0].generate()
+        } else {
+            self.p[1].generate()
+        }
+    }
+}
+
+// Cosine-weighted direction around +z.
+pub fn random_cosine_direction() -> Vec3 {
+    let r1 = random_double();
+    let r2 = random_double();
+
+    let phi = 2.0 * PI * r1;
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
+    let z = (1.0 - r2).sqrt();
+    Vec3::new(x, y, z)
+}
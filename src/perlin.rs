@@ -0,0 +1,122 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::vec3::{Point3, Vec3};
+
+const POINT_COUNT: usize = 256;
+
+// Gradient noise on a 3D lattice. The random vectors and permutation tables
+// come from a seeded generator, so equal seeds give identical patterns.
+pub struct Perlin{
+    randvec: [Vec3; POINT_COUNT],
+    perm_x: [usize; POINT_COUNT],
+    perm_y: [usize; POINT_COUNT],
+    perm_z: [usize; POINT_COUNT],
+}
+
+impl Perlin{
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut randvec = [Vec3::new(0.0, 0.0, 0.0); POINT_COUNT];
+        for v in randvec.iter_mut() {
+            *v = loop {
+                let p = Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                );
+                let lensq = p.length_squared();
+                if 1e-160 < lensq && lensq <= 1.0 {
+                    break p.scalar_div(lensq.sqrt())
+                }
+            };
+        }
+
+        let mut permute = || {
+            let mut p: [usize; POINT_COUNT] = std::array::from_fn(|i| i);
+            p.shuffle(&mut rng);
+            p
+        };
+        let (perm_x, perm_y, perm_z) = (permute(), permute(), permute());
+
+        Perlin { randvec, perm_x, perm_y, perm_z }
+    }
+
+    // Smooth noise in roughly [-1, 1].
+    pub fn noise(&self, p: Point3) -> f64 {
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+
+        let mut c = [[[Vec3::new(0.0, 0.0, 0.0); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize];
+                    *corner = self.randvec[index];
+                }
+            }
+        }
+
+        Perlin::perlin_interp(&c, u, v, w)
+    }
+
+    // Sum of `depth` octaves of |noise|, each at twice the frequency and
+    // half the weight of the last.
+    pub fn turbulence(&self, p: Point3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(temp_p).abs();
+            weight *= 0.5;
+            temp_p = temp_p.scalar_mul(2.0);
+        }
+
+        accum
+    }
+
+    // Fractal Brownian motion: signed octaves scaled by `lacunarity` in
+    // frequency and `gain` in amplitude.
+    pub fn fbm(&self, p: Point3, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..octaves {
+            accum += weight * self.noise(temp_p);
+            weight *= gain;
+            temp_p = temp_p.scalar_mul(lacunarity);
+        }
+
+        accum
+    }
+
+    fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+        let mut accum = 0.0;
+
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, corner) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * Vec3::dot_product(*corner, weight_v);
+                }
+            }
+        }
+
+        accum
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// An infinite plane through `point`. Texture coordinates are the distances
+// along two tangent axes divided by `uv_scale`, so they keep growing across
+// the plane; use a repeating or spatial texture on it.
+pub struct Plane{
+    pub point : Point3,
+    pub normal : Vec3,
+    pub uv_scale : f64,
+    pub mat: Arc<dyn Material>,
+    tangent : Vec3,
+    bitangent : Vec3,
+    bbox : Aabb,
+}
+
+impl Plane{
+    pub fn new(point: Point3, normal: Vec3, mat: Arc<dyn Material>) -> Plane {
+        Plane::with_uv_scale(point, normal, 1.0, mat)
+    }
+
+    pub fn with_uv_scale(point: Point3, normal: Vec3, uv_scale: f64, mat: Arc<dyn Material>) -> Plane {
+        let normal = normal.unit_vector();
+        let helper = if normal.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let bitangent = Vec3::cross_product(normal, helper).unit_vector();
+        let tangent = Vec3::cross_product(bitangent, normal);
+
+        // Only an axis-aligned plane has a box that is thin along one axis.
+        let mut bbox = Aabb::UNIVERSE;
+        let axis_aligned = (0..3).find(|&axis| normal.e[axis].abs() > 1.0 - 1e-12);
+        if let Some(axis) = axis_aligned {
+            let slab = Interval::new(point.e[axis], point.e[axis]).expand(0.0001);
+            match axis {
+                0 => bbox.x = slab,
+                1 => bbox.y = slab,
+                _ => bbox.z = slab,
+            }
+        }
+
+        Plane { point, normal, uv_scale, mat, tangent, bitangent, bbox }
+    }
+}
+
+impl Hittable for Plane{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = Vec3::dot_product(self.normal, r.direction());
+        if denom.abs() < 1e-8 {
+            return false
+        }
+
+        let t = Vec3::dot_product(self.normal, self.point - r.origin()) / denom;
+        if !Interval::surrounds(&ray_t, t) {
+            return false
+        }
+
+        rec.t = t;
+        rec.p = r.ray_at(t);
+        let offset = rec.p - self.point;
+        rec.u = Vec3::dot_product(offset, self.tangent) / self.uv_scale;
+        rec.v = Vec3::dot_product(offset, self.bitangent) / self.uv_scale;
+        rec.mat = self.mat.clone();
+        rec.set_face_normal(r, self.normal);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+
+    #[test]
+    fn hits_and_uvs() {
+        let plane = Plane::with_uv_scale(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 2.0, Arc::new(EmptyMaterial));
+        let mut rec = HitRecord::new();
+
+        let r = Ray::new(Point3::new(3.0, 2.0, 4.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(plane.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).near_zero());
+        // Distances along the tangent axes, here x and -z, over uv_scale.
+        assert!((rec.u - 1.5).abs() < 1e-12 && (rec.v + 2.0).abs() < 1e-12);
+
+        let r = Ray::new(Point3::new(3.0, -2.0, 4.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(plane.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, -1.0, 0.0)).near_zero());
+
+        let r = Ray::new(Point3::new(3.0, 2.0, 4.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!plane.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+    }
+
+    #[test]
+    fn uvs_locate_the_hit_on_a_tilted_plane() {
+        let point = Point3::new(1.0, 2.0, 3.0);
+        let plane = Plane::with_uv_scale(point, Vec3::new(1.0, 2.0, -2.0), 0.5, Arc::new(EmptyMaterial));
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(-4.0, 7.0, 1.0), Vec3::new(0.3, -1.0, 0.2));
+        assert!(plane.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!(Vec3::dot_product(rec.p - point, plane.normal).abs() < 1e-9);
+        let rebuilt = point + (plane.tangent.scalar_mul(rec.u * 0.5) + plane.bitangent.scalar_mul(rec.v * 0.5));
+        assert!((rebuilt - rec.p).near_zero());
+    }
+}
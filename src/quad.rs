@@ -0,0 +1,273 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{area_pdf_to_solid_angle, HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::random_double;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// Region of the plane through `q` spanned by `u` and `v` that a `Quad`
+// covers, in terms of the planar coordinates (alpha, beta) of a hit point
+// p = q + alpha * u + beta * v.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlanarShape {
+    // 0 <= alpha, beta <= 1.
+    Parallelogram,
+    // alpha, beta >= 0 and alpha + beta <= 1: the triangle q, q+u, q+v.
+    Triangle,
+    // alpha² + beta² <= 1: an ellipse centred on q with semi-axes u and v.
+    Disk,
+}
+
+pub struct Quad{
+    pub q : Point3,
+    pub u : Vec3,
+    pub v : Vec3,
+    pub shape: PlanarShape,
+    pub mat: Arc<dyn Material>,
+    w : Vec3,
+    normal : Vec3,
+    d : f64,
+    area : f64,
+    bbox : Aabb,
+}
+
+impl Quad{
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Quad {
+        Quad::with_shape(q, u, v, PlanarShape::Parallelogram, mat)
+    }
+
+    pub fn triangle(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Quad {
+        Quad::with_shape(q, u, v, PlanarShape::Triangle, mat)
+    }
+
+    pub fn disk(center: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Quad {
+        Quad::with_shape(center, u, v, PlanarShape::Disk, mat)
+    }
+
+    pub fn with_shape(q: Point3, u: Vec3, v: Vec3, shape: PlanarShape, mat: Arc<dyn Material>) -> Quad {
+        let n = Vec3::cross_product(u, v);
+        let normal = n.unit_vector();
+        let d = Vec3::dot_product(normal, q);
+        let w = n.scalar_div(Vec3::dot_product(n, n));
+        let area = match shape {
+            PlanarShape::Parallelogram => n.length(),
+            PlanarShape::Triangle => 0.5 * n.length(),
+            PlanarShape::Disk => std::f64::consts::PI * n.length(),
+        };
+
+        let bbox = match shape {
+            PlanarShape::Parallelogram | PlanarShape::Triangle => {
+                let diagonal1 = Aabb::from_points(q, q + u + v);
+                let diagonal2 = Aabb::from_points(q + u, q + v);
+                Aabb::enclosing(&diagonal1, &diagonal2)
+            }
+            PlanarShape::Disk => {
+                // Extent of the ellipse q + cos(t) u + sin(t) v along each axis.
+                let half = Vec3::new(
+                    (u.x() * u.x() + v.x() * v.x()).sqrt(),
+                    (u.y() * u.y() + v.y() * v.y()).sqrt(),
+                    (u.z() * u.z() + v.z() * v.z()).sqrt(),
+                );
+                Aabb::from_points(q - half, q + half)
+            }
+        };
+
+        Quad { q, u, v, shape, mat, w, normal, d, area, bbox }
+    }
+
+    // Returns the texture coordinates of the hit if (alpha, beta) lies
+    // inside the shape.
+    fn is_interior(&self, alpha: f64, beta: f64) -> Option<(f64, f64)> {
+        let unit_interval = Interval::new(0.0, 1.0);
+        match self.shape {
+            PlanarShape::Parallelogram => {
+                (unit_interval.contains(alpha) && unit_interval.contains(beta)).then_some((alpha, beta))
+            }
+            PlanarShape::Triangle => {
+                (alpha >= 0.0 && beta >= 0.0 && alpha + beta <= 1.0).then_some((alpha, beta))
+            }
+            PlanarShape::Disk => {
+                (alpha * alpha + beta * beta <= 1.0).then_some((0.5 * (alpha + 1.0), 0.5 * (beta + 1.0)))
+            }
+        }
+    }
+}
+
+impl Hittable for Quad{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = Vec3::dot_product(self.normal, r.direction());
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return false
+        }
+
+        let t = (self.d - Vec3::dot_product(self.normal, r.origin())) / denom;
+        if !Interval::surrounds(&ray_t, t) {
+            return false
+        }
+
+        let intersection = r.ray_at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = Vec3::dot_product(self.w, Vec3::cross_product(planar_hitpt_vector, self.v));
+        let beta = Vec3::dot_product(self.w, Vec3::cross_product(self.u, planar_hitpt_vector));
+
+        let Some((u, v)) = self.is_interior(alpha, beta) else {
+            return false
+        };
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = u;
+        rec.v = v;
+        rec.mat = self.mat.clone();
+        rec.set_face_normal(r, self.normal);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0
+        }
+        area_pdf_to_solid_angle(direction, rec.t, self.normal, self.area)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let (alpha, beta) = self.random_planar();
+        self.point_at(alpha, beta) - origin
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        let (alpha, beta) = self.random_planar();
+        let mut rec = HitRecord::new();
+        (rec.u, rec.v) = self.is_interior(alpha, beta)?;
+        rec.p = self.point_at(alpha, beta);
+        rec.normal = self.normal;
+        rec.front_face = true;
+        rec.mat = self.mat.clone();
+        Some((rec, 1.0 / self.area))
+    }
+}
+
+impl Quad{
+    // Planar coordinates of a uniformly random point inside the shape.
+    fn random_planar(&self) -> (f64, f64) {
+        match self.shape {
+            PlanarShape::Parallelogram => (random_double(), random_double()),
+            PlanarShape::Triangle => {
+                // Fold the far half of the parallelogram back onto the triangle.
+                let (a, b) = (random_double(), random_double());
+                if a + b > 1.0 { (1.0 - a, 1.0 - b) } else { (a, b) }
+            }
+            PlanarShape::Disk => {
+                let p = Vec3::random_in_unit_disk();
+                (p.x(), p.y())
+            }
+        }
+    }
+
+    fn point_at(&self, alpha: f64, beta: f64) -> Point3 {
+        self.q + (self.u.scalar_mul(alpha) + self.v.scalar_mul(beta))
+    }
+}
+
+// The six faces of the axis-aligned box with opposite corners `a` and `b`.
+pub fn make_box(a: Point3, b: Point3, mat: Arc<dyn Material>) -> HittableList {
+    let mut sides = HittableList::new();
+
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    sides.add(Arc::new(Quad::new(Point3::new(min.x(), min.y(), max.z()), dx, dy, mat.clone()))); // front
+    sides.add(Arc::new(Quad::new(Point3::new(max.x(), min.y(), max.z()), dz.scalar_mul(-1.0), dy, mat.clone()))); // right
+    sides.add(Arc::new(Quad::new(Point3::new(max.x(), min.y(), min.z()), dx.scalar_mul(-1.0), dy, mat.clone()))); // back
+    sides.add(Arc::new(Quad::new(Point3::new(min.x(), min.y(), min.z()), dz, dy, mat.clone()))); // left
+    sides.add(Arc::new(Quad::new(Point3::new(min.x(), max.y(), max.z()), dx, dz.scalar_mul(-1.0), mat.clone()))); // top
+    sides.add(Arc::new(Quad::new(Point3::new(min.x(), min.y(), min.z()), dx, dz, mat))); // bottom
+
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+
+    // Fires a ray straight down -z at (x, y) and returns the hit, if any.
+    fn hit_at(shape: &Quad, x: f64, y: f64) -> Option<HitRecord> {
+        let r = Ray::new(Point3::new(x, y, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::new();
+        shape.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec).then_some(rec)
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn parallelogram_uvs_are_planar_coordinates() {
+        let quad = Quad::new(Point3::new(1.0, 1.0, 2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 4.0, 0.0), Arc::new(EmptyMaterial));
+        // q + 0.25 u + 0.5 v
+        let rec = hit_at(&quad, 2.0, 3.0).unwrap();
+        assert!(close(rec.t, 3.0));
+        assert!(close(rec.u, 0.25) && close(rec.v, 0.5));
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).near_zero());
+        // Outside the slanted side, though inside the bounding box.
+        assert!(hit_at(&quad, 1.2, 4.5).is_none());
+    }
+
+    #[test]
+    fn triangle_and_disk_cover_their_regions() {
+        let (q, u, v) = (Point3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0));
+        let triangle = Quad::triangle(q, u, v, Arc::new(EmptyMaterial));
+        let rec = hit_at(&triangle, 0.5, 1.0).unwrap();
+        assert!(close(rec.u, 0.25) && close(rec.v, 0.5));
+        assert!(hit_at(&triangle, 1.5, 1.0).is_none());
+
+        // Disk uvs map the unit disk onto the unit square, centre at 0.5.
+        let disk = Quad::disk(q, u, v, Arc::new(EmptyMaterial));
+        let rec = hit_at(&disk, 0.0, 0.0).unwrap();
+        assert!(close(rec.u, 0.5) && close(rec.v, 0.5));
+        let rec = hit_at(&disk, -1.0, 1.0).unwrap();
+        assert!(close(rec.u, 0.25) && close(rec.v, 0.75));
+        assert!(hit_at(&disk, 1.5, 1.5).is_none());
+    }
+
+    #[test]
+    fn box_faces_point_outward() {
+        let (min, max) = (Point3::new(-1.0, 0.0, 2.0), Point3::new(3.0, 1.0, 5.0));
+        let center = (min + max).scalar_mul(0.5);
+        let faces = make_box(min, max, Arc::new(EmptyMaterial));
+        assert_eq!(faces.objects.len(), 6);
+        let mut normals = Vec::new();
+        for face in &faces.objects {
+            let (rec, _) = face.sample_surface().unwrap();
+            assert!(Vec3::dot_product(rec.normal, rec.p - center) > 0.0);
+
+            // A ray from outside towards the centre hits the front face.
+            let origin = rec.p + rec.normal;
+            let mut hit = HitRecord::new();
+            assert!(face.hit(&Ray::new(origin, center - origin), Interval::new(0.001, f64::INFINITY), &mut hit));
+            assert!(hit.front_face);
+            normals.push(rec.normal);
+        }
+        // One face per direction.
+        for (i, a) in normals.iter().enumerate() {
+            assert!(normals[i + 1..].iter().all(|b| !(*a - *b).near_zero()));
+        }
+    }
+}
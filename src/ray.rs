@@ -3,11 +3,16 @@ use crate::vec3::{Point3, Vec3};
 pub struct Ray{
     pub origin : Point3,
     pub direction : Vec3,
+    pub time : f64,
 }
 
 impl Ray{
     pub fn new(a : Point3 , b : Vec3) -> Ray {
-        Ray { origin: a, direction: b }
+        Ray::with_time(a, b, 0.0)
+    }
+
+    pub fn with_time(a : Point3 , b : Vec3, time : f64) -> Ray {
+        Ray { origin: a, direction: b, time }
     }
 
     pub fn origin(&self) -> Point3 {
@@ -18,7 +23,11 @@ impl Ray{
         self.direction
     }
 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
     pub fn ray_at(&self, t: f64) -> Vec3 {
-        Vec3::add(self.origin, self.direction.scalar_mul(t))
+        self.origin + self.direction.scalar_mul(t)
     }
 }
\ No newline at end of file
@@ -0,0 +1,1073 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::aabb::Aabb;
+use crate::background::Background;
+use crate::bdpt::BidirectionalPathTracer;
+use crate::camera::{Camera, CameraSettings};
+use crate::constant_medium::ConstantMedium;
+use crate::environment::EnvironmentMap;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::integrator::{AmbientOcclusion, DebugChannel, DebugIntegrator, NaivePathTracer, PathTracer, Whitted};
+use crate::light::{AreaLight, DirectionalLight, Light, LightBounds, LightList, PointLight, SpotLight};
+use crate::mat4::Mat4;
+use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::obj::{load_obj, ObjError};
+use crate::pbr::{Conductor, Pbr};
+use crate::plane::Plane;
+use crate::quad::{make_box, PlanarShape, Quad};
+use crate::sky::Sky;
+use crate::sphere::Sphere;
+use crate::texture::{CheckerMapping, CheckerTexture, Filter, ImageTexture, NoisePattern, NoiseTexture, SolidColor, Texture, WrapMode};
+use crate::transform::Transform;
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+
+pub struct Scene{
+    pub camera: Camera,
+    pub world: HittableList,
+    // Emitters from `world` that the camera samples directly.
+    pub lights: LightList,
+    // Problems that didn't stop the load, for the caller to report.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { path: PathBuf, source: std::io::Error },
+    Parse { path: PathBuf, message: String },
+    UnknownMaterial { context: String, name: String },
+    UnknownTexture { material: String, name: String },
+    Texture { path: PathBuf, message: String },
+    Invalid { context: String, message: String },
+    Obj(ObjError),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Parse { path, message } => write!(f, "{}: {}", path.display(), message),
+            SceneError::UnknownMaterial { context, name } => {
+                write!(f, "{}: unknown material '{}'", context, name)
+            }
+            SceneError::UnknownTexture { material, name } => {
+                write!(f, "materials.{}: unknown texture '{}'", material, name)
+            }
+            SceneError::Texture { path, message } => write!(f, "{}: {}", path.display(), message),
+            SceneError::Invalid { context, message } => write!(f, "{}: {}", context, message),
+            SceneError::Obj(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Obj(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ObjError> for SceneError {
+    fn from(err: ObjError) -> Self {
+        SceneError::Obj(err)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    #[serde(default)]
+    camera: CameraDesc,
+    background: Option<BackgroundDesc>,
+    integrator: Option<IntegratorDesc>,
+    #[serde(default)]
+    textures: HashMap<String, TextureDesc>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    #[serde(default)]
+    shapes: HashMap<String, ObjectDesc>,
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct CameraDesc {
+    aspect_ratio: f64,
+    image_width: i64,
+    samples_per_pixel: i64,
+    max_depth: i64,
+    vfov: i64,
+    lookfrom: [f64; 3],
+    lookat: [f64; 3],
+    vup: [f64; 3],
+    defocus_angle: f64,
+    focus_dist: f64,
+    shutter_open: f64,
+    shutter_close: f64,
+}
+
+// Unset keys take the defaults of a camera built in code.
+impl Default for CameraDesc {
+    fn default() -> Self {
+        let settings = CameraSettings::default();
+        CameraDesc {
+            aspect_ratio: settings.aspect_ratio,
+            image_width: settings.image_width,
+            samples_per_pixel: settings.samples_per_pixel,
+            max_depth: settings.max_depth,
+            vfov: settings.vfov,
+            lookfrom: settings.lookfrom.e,
+            lookat: settings.lookat.e,
+            vup: settings.vup.e,
+            defocus_angle: settings.defocus_angle,
+            focus_dist: settings.focus_dist,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundDesc {
+    Black,
+    Solid { color: [f64; 3] },
+    Gradient { bottom: [f64; 3], top: [f64; 3] },
+    // Equirectangular .hdr or .exr map; rotation is in degrees about Y.
+    Environment {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    // Preetham daylight sky; angles are in degrees. The sun is added to the
+    // lights.
+    Sky {
+        sun_elevation: f64,
+        #[serde(default)]
+        sun_azimuth: f64,
+        #[serde(default = "default_turbidity")]
+        turbidity: f64,
+        #[serde(default = "default_sky_intensity")]
+        intensity: f64,
+        #[serde(default = "default_sun_diameter")]
+        sun_diameter: f64,
+    },
+}
+
+// The light transport algorithm; `path` when absent.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum IntegratorDesc {
+    Path,
+    Naive,
+    Bdpt,
+    Whitted,
+    // Occluders further than `max_distance` don't count.
+    AmbientOcclusion {
+        #[serde(default = "default_ao_distance")]
+        max_distance: f64,
+    },
+    Normals,
+    Albedo,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid { color: [f64; 3] },
+    Checker {
+        #[serde(default = "default_checker_mapping")]
+        mapping: CheckerMappingDesc,
+        scale: f64,
+        even: [f64; 3],
+        odd: [f64; 3],
+    },
+    Image {
+        path: PathBuf,
+        #[serde(default = "default_wrap")]
+        wrap: WrapModeDesc,
+        #[serde(default = "default_filter")]
+        filter: FilterDesc,
+        // Set for data maps (roughness, metallic) that aren't sRGB colours.
+        #[serde(default)]
+        linear: bool,
+    },
+    Noise {
+        pattern: NoisePatternDesc,
+        #[serde(default = "default_frequency")]
+        frequency: f64,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_color_a")]
+        color_a: [f64; 3],
+        #[serde(default = "default_color_b")]
+        color_b: [f64; 3],
+    },
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum NoisePatternDesc { Noise, Turbulence, Fbm, Marble, Wood }
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum CheckerMappingDesc { Spatial, Uv }
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum WrapModeDesc { Repeat, MirroredRepeat, Clamp }
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum FilterDesc { Nearest, Bilinear }
+
+fn default_checker_mapping() -> CheckerMappingDesc { CheckerMappingDesc::Spatial }
+fn default_wrap() -> WrapModeDesc { WrapModeDesc::Repeat }
+fn default_filter() -> FilterDesc { FilterDesc::Bilinear }
+fn default_frequency() -> f64 { 1.0 }
+fn default_octaves() -> u32 { 7 }
+fn default_color_a() -> [f64; 3] { [0.0, 0.0, 0.0] }
+fn default_color_b() -> [f64; 3] { [1.0, 1.0, 1.0] }
+fn default_uv_scale() -> f64 { 1.0 }
+fn default_intensity() -> f64 { 1.0 }
+fn default_ao_distance() -> f64 { f64::INFINITY }
+fn default_light_color() -> [f64; 3] { [1.0, 1.0, 1.0] }
+fn default_turbidity() -> f64 { 3.0 }
+fn default_sky_intensity() -> f64 { 0.05 }
+fn default_sun_diameter() -> f64 { 0.53 }
+fn default_metallic() -> ScalarInput { ScalarInput::Value(0.0) }
+fn default_roughness() -> ScalarInput { ScalarInput::Value(0.5) }
+
+// A material input given either as an RGB triple or as the name of a
+// texture from the `[textures]` table.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorInput {
+    Color([f64; 3]),
+    Texture(String),
+}
+
+// Same for single-channel inputs; textures are read from the red channel.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScalarInput {
+    Value(f64),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian { albedo: ColorInput },
+    Metal { albedo: ColorInput, #[serde(default)] fuzz: f64 },
+    Dielectric { refraction_index: f64 },
+    DiffuseLight { emit: ColorInput },
+    Isotropic { albedo: ColorInput },
+    Pbr {
+        base_color: ColorInput,
+        #[serde(default = "default_metallic")]
+        metallic: ScalarInput,
+        #[serde(default = "default_roughness")]
+        roughness: ScalarInput,
+        // Complex index of refraction per channel, for conductor Fresnel.
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    // `center_end` makes the sphere move from `center` at time 0 to
+    // `center_end` at time 1.
+    Sphere { center: [f64; 3], center_end: Option<[f64; 3]>, radius: f64, material: String },
+    Triangle { vertices: [[f64; 3]; 3], material: String },
+    Quad { corner: [f64; 3], u: [f64; 3], v: [f64; 3], material: String },
+    Disk { center: [f64; 3], u: [f64; 3], v: [f64; 3], material: String },
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+        #[serde(default = "default_uv_scale")]
+        uv_scale: f64,
+        material: String,
+    },
+    Box { min: [f64; 3], max: [f64; 3], material: String },
+    Obj { path: PathBuf },
+    // Fills a named entry from `[shapes]` with a scattering volume; use an
+    // `isotropic` material.
+    ConstantMedium { boundary: String, density: f64, material: String },
+    // Places a named entry from `[shapes]`. Either `matrix` (row-major,
+    // object to world) or any of scale, rotate and translate may be given;
+    // the latter apply in that order, with rotate in degrees about x, y
+    // then z.
+    Instance {
+        shape: String,
+        scale: Option<[f64; 3]>,
+        rotate: Option<[f64; 3]>,
+        translate: Option<[f64; 3]>,
+        matrix: Option<[[f64; 4]; 4]>,
+    },
+}
+
+// Explicit lights. Power is in watts, irradiance in W/m² and angles in
+// degrees; `rect` and `sphere` lights are also added to the world.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDesc {
+    Point {
+        position: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: [f64; 3],
+        power: f64,
+    },
+    // `inner_angle` and `outer_angle` are measured from the axis; the light
+    // fades out between them.
+    Spot {
+        position: [f64; 3],
+        direction: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: [f64; 3],
+        power: f64,
+        #[serde(default)]
+        inner_angle: f64,
+        outer_angle: f64,
+    },
+    // `direction` is the way the light travels.
+    Directional {
+        direction: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: [f64; 3],
+        irradiance: f64,
+    },
+    // Emits from the side `u × v` points to.
+    Rect {
+        corner: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: [f64; 3],
+        power: f64,
+    },
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        #[serde(default = "default_light_color")]
+        color: [f64; 3],
+        power: f64,
+    },
+}
+
+// Reads a TOML scene description. Relative paths inside the file (such as
+// OBJ models) are resolved against the scene file's directory.
+pub fn load_scene(path: &Path) -> Result<Scene, SceneError> {
+    let source = fs::read_to_string(path)
+        .map_err(|source| SceneError::Io { path: path.to_path_buf(), source })?;
+    parse_scene(&source, path)
+}
+
+// Builds a scene from the contents of the file at `path`.
+fn parse_scene(source: &str, path: &Path) -> Result<Scene, SceneError> {
+    let desc: SceneDesc = toml::from_str(source)
+        .map_err(|err| SceneError::Parse { path: path.to_path_buf(), message: err.to_string() })?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut camera = build_camera(&desc.camera)?;
+    if let Some(integrator) = &desc.integrator {
+        camera.integrator = match integrator {
+            IntegratorDesc::Path => Box::new(PathTracer),
+            IntegratorDesc::Naive => Box::new(NaivePathTracer),
+            IntegratorDesc::Bdpt => Box::new(BidirectionalPathTracer),
+            IntegratorDesc::Whitted => Box::new(Whitted),
+            IntegratorDesc::AmbientOcclusion { max_distance } => {
+                if *max_distance <= 0.0 {
+                    return Err(invalid("integrator".to_string(), "max_distance must be positive"))
+                }
+                Box::new(AmbientOcclusion::new(*max_distance))
+            }
+            IntegratorDesc::Normals => Box::new(DebugIntegrator::new(DebugChannel::Normals)),
+            IntegratorDesc::Albedo => Box::new(DebugIntegrator::new(DebugChannel::Albedo)),
+        };
+    }
+    let mut lights = LightList::new();
+    if let Some(background) = &desc.background {
+        camera.background = match background {
+            BackgroundDesc::Black => Background::black(),
+            BackgroundDesc::Solid { color } => Background::Solid(to_vec3(color)),
+            BackgroundDesc::Gradient { bottom, top } => Background::Gradient { bottom: to_vec3(bottom), top: to_vec3(top) },
+            BackgroundDesc::Environment { path, rotation, intensity } => {
+                if *intensity < 0.0 {
+                    return Err(invalid("background".to_string(), "intensity must not be negative"))
+                }
+                let path = base_dir.join(path);
+                let map = EnvironmentMap::open(&path, *rotation, *intensity)
+                    .map_err(|err| SceneError::Texture { path: path.clone(), message: err.to_string() })?;
+                let map = Arc::new(map);
+                lights.add(map.clone());
+                Background::Environment(map)
+            }
+            BackgroundDesc::Sky { sun_elevation, sun_azimuth, turbidity, intensity, sun_diameter } => {
+                if !(1.7..=10.0).contains(turbidity) {
+                    return Err(invalid("background".to_string(), "turbidity must be between 1.7 and 10"))
+                }
+                if !(-90.0..=90.0).contains(sun_elevation) {
+                    return Err(invalid("background".to_string(), "sun_elevation must be between -90 and 90"))
+                }
+                if *intensity < 0.0 {
+                    return Err(invalid("background".to_string(), "intensity must not be negative"))
+                }
+                if *sun_diameter <= 0.0 || *sun_diameter >= 180.0 {
+                    return Err(invalid("background".to_string(), "sun_diameter must be between 0 and 180"))
+                }
+                let sky = Arc::new(Sky::with_sun_size(*sun_elevation, *sun_azimuth, *turbidity, *intensity, *sun_diameter));
+                if *sun_elevation > 0.0 {
+                    lights.add(Arc::new(sky.sun));
+                }
+                Background::Sky(sky)
+            }
+        };
+    }
+
+    let mut textures: HashMap<&str, Arc<dyn Texture>> = HashMap::new();
+    for (name, tex) in &desc.textures {
+        textures.insert(name, build_texture(name, tex, base_dir)?);
+    }
+
+    let mut materials: HashMap<&str, Arc<dyn Material>> = HashMap::new();
+    for (name, mat) in &desc.materials {
+        materials.insert(name, build_material(name, mat, &textures)?);
+    }
+
+    // Shapes are built once and shared by every instance that places them.
+    // Shapes may refer to other shapes, so keep building whichever ones have
+    // their dependency ready until nothing is left.
+    let mut warnings = Vec::new();
+    let mut shapes: HashMap<&str, Arc<dyn Hittable>> = HashMap::new();
+    let mut pending: Vec<(&String, &ObjectDesc)> = desc.shapes.iter().collect();
+    pending.sort_by_key(|(name, _)| *name);
+    while !pending.is_empty() {
+        let (ready, blocked): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(_, shape)| {
+            shape_dependency(shape).is_none_or(|dep| shapes.contains_key(dep.as_str()))
+        });
+        if ready.is_empty() {
+            let (name, shape) = blocked[0];
+            let dep = shape_dependency(shape).unwrap();
+            let message = if desc.shapes.contains_key(dep) { "shapes refer to each other in a cycle" } else { "unknown shape" };
+            return Err(invalid(format!("shapes.{}", name), &format!("{} '{}'", message, dep)))
+        }
+        for (name, shape) in ready {
+            let context = format!("shapes.{}", name);
+            let built = build_object(&context, shape, &materials, &shapes, base_dir, &mut warnings)?;
+            shapes.insert(name, built);
+        }
+        pending = blocked;
+    }
+
+    let mut world = HittableList::new();
+    let tables = Tables { desc: &desc, shapes: &shapes, materials: &materials, textures: &textures };
+    for (index, object) in desc.objects.iter().enumerate() {
+        let context = format!("objects[{}]", index);
+        let built = build_object(&context, object, &materials, &shapes, base_dir, &mut warnings)?;
+        for (surface, radiance, planar) in emissive_surfaces(&context, object, &built, &tables, &mut warnings)? {
+            if let Some(bounds) = surface_light_bounds(&surface, radiance, planar) {
+                lights.add(Arc::new(AreaLight::with_bounds(surface, bounds)));
+            }
+        }
+        world.add(built);
+    }
+    for (index, light) in desc.lights.iter().enumerate() {
+        let context = format!("lights[{}]", index);
+        lights.add(build_light(context, light, &mut world)?);
+    }
+
+    Ok(Scene { camera, world, lights, warnings })
+}
+
+// Area lights also add their geometry to `world`.
+fn build_light(context: String, desc: &LightDesc, world: &mut HittableList) -> Result<Arc<dyn Light>, SceneError> {
+    let power = match desc {
+        LightDesc::Point { power, .. }
+        | LightDesc::Spot { power, .. }
+        | LightDesc::Rect { power, .. }
+        | LightDesc::Sphere { power, .. } => *power,
+        LightDesc::Directional { irradiance, .. } => *irradiance,
+    };
+    if power < 0.0 {
+        return Err(invalid(context, "light power must not be negative"))
+    }
+    Ok(match desc {
+        LightDesc::Point { position, color, power } => {
+            Arc::new(PointLight::new(to_vec3(position), to_vec3(color), *power))
+        }
+        LightDesc::Spot { position, direction, color, power, inner_angle, outer_angle } => {
+            if *outer_angle <= 0.0 || *outer_angle > 180.0 || !(0.0..=*outer_angle).contains(inner_angle) {
+                return Err(invalid(context, "spot light angles need 0 <= inner_angle <= outer_angle, 0 < outer_angle <= 180"))
+            }
+            if to_vec3(direction).near_zero() {
+                return Err(invalid(context, "spot light direction must not be zero"))
+            }
+            Arc::new(SpotLight::new(to_vec3(position), to_vec3(direction), to_vec3(color), *power, *inner_angle, *outer_angle))
+        }
+        LightDesc::Directional { direction, color, irradiance } => {
+            if to_vec3(direction).near_zero() {
+                return Err(invalid(context, "directional light direction must not be zero"))
+            }
+            Arc::new(DirectionalLight::new(to_vec3(direction), to_vec3(color), *irradiance))
+        }
+        LightDesc::Rect { corner, u, v, color, power } => {
+            if Vec3::cross_product(to_vec3(u), to_vec3(v)).near_zero() {
+                return Err(invalid(context, "rect light u and v must span an area"))
+            }
+            let light = AreaLight::rect(to_vec3(corner), to_vec3(u), to_vec3(v), to_vec3(color), *power);
+            world.add(light.shape.clone());
+            Arc::new(light)
+        }
+        LightDesc::Sphere { center, radius, color, power } => {
+            if *radius <= 0.0 {
+                return Err(invalid(context, "sphere light radius must be positive"))
+            }
+            let light = AreaLight::sphere(to_vec3(center), *radius, to_vec3(color), *power);
+            world.add(light.shape.clone());
+            Arc::new(light)
+        }
+    })
+}
+
+// A surface to sample as a light, its radiance and whether it is flat.
+type EmissiveSurface = (Arc<dyn Hittable>, f64, bool);
+
+// The scene description and what has been built from it by name, for
+// following an object's references back to its material.
+struct Tables<'a> {
+    desc: &'a SceneDesc,
+    shapes: &'a HashMap<&'a str, Arc<dyn Hittable>>,
+    materials: &'a HashMap<&'a str, Arc<dyn Material>>,
+    textures: &'a HashMap<&'a str, Arc<dyn Texture>>,
+}
+
+// Emissive surfaces of an object that light sampling can pick. Boxes give
+// one surface per face and instances wrap their shape's surfaces in the
+// same transform. OBJ models have no emissive materials, and moving spheres
+// and planes are skipped with a warning: light samples carry no time, and a
+// plane has no finite area.
+fn emissive_surfaces(
+    context: &str,
+    object: &ObjectDesc,
+    built: &Arc<dyn Hittable>,
+    tables: &Tables,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<EmissiveSurface>, SceneError> {
+    let radiance = |material: &String| emitted_radiance(material, built.bounding_box(), tables.desc, tables.textures);
+    Ok(match object {
+        ObjectDesc::Sphere { center_end: None, material, .. } => {
+            radiance(material).map(|radiance| (built.clone(), radiance, false)).into_iter().collect()
+        }
+        ObjectDesc::Triangle { material, .. } | ObjectDesc::Quad { material, .. } | ObjectDesc::Disk { material, .. } => {
+            radiance(material).map(|radiance| (built.clone(), radiance, true)).into_iter().collect()
+        }
+        ObjectDesc::Box { min, max, material } => match (radiance(material), tables.materials.get(material.as_str())) {
+            (Some(radiance), Some(mat)) => make_box(to_vec3(min), to_vec3(max), mat.clone())
+                .objects
+                .into_iter()
+                .map(|face| (face, radiance, true))
+                .collect(),
+            _ => Vec::new(),
+        },
+        ObjectDesc::Sphere { material, .. } | ObjectDesc::Plane { material, .. } => {
+            if radiance(material).is_some() {
+                warnings.push(format!("{}: moving or unbounded emitters are not sampled as lights", context));
+            }
+            Vec::new()
+        }
+        ObjectDesc::Instance { shape, scale, rotate, translate, matrix } => {
+            let (Some(inner), Some(inner_built)) = (tables.desc.shapes.get(shape), tables.shapes.get(shape.as_str())) else {
+                return Ok(Vec::new())
+            };
+            let to_world = instance_matrix(context, *scale, *rotate, *translate, *matrix)?;
+            let mut surfaces = Vec::new();
+            for (surface, radiance, planar) in emissive_surfaces(context, inner, inner_built, tables, warnings)? {
+                // A surface sampled uniformly stays uniform only if the
+                // transform stretches it evenly everywhere.
+                if !planar && !is_similarity(&to_world) {
+                    warnings.push(format!("{}: non-uniformly scaled curved emitters are not sampled as lights", context));
+                    continue
+                }
+                if let Some(transform) = Transform::new(surface, to_world) {
+                    surfaces.push((Arc::new(transform) as Arc<dyn Hittable>, radiance, planar));
+                }
+            }
+            surfaces
+        }
+        ObjectDesc::Obj { .. } | ObjectDesc::ConstantMedium { .. } => Vec::new(),
+    })
+}
+
+// The brightest channel of a `diffuse_light` material's radiance, or None
+// for other materials. Textured emitters are judged by their mean over the
+// uv square, evaluated at the centre of `bbox` for spatial textures.
+fn emitted_radiance(material: &String, bbox: Aabb, desc: &SceneDesc, textures: &HashMap<&str, Arc<dyn Texture>>) -> Option<f64> {
+    let Some(MaterialDesc::DiffuseLight { emit }) = desc.materials.get(material) else {
+        return None
+    };
+    let radiance = match emit {
+        ColorInput::Color(color) => to_vec3(color),
+        ColorInput::Texture(name) => {
+            const GRID: usize = 16;
+            let texture = textures.get(name.as_str())?;
+            let center = Vec3::new(bbox.centroid(0), bbox.centroid(1), bbox.centroid(2));
+            let mut sum = Vec3::new(0.0, 0.0, 0.0);
+            for i in 0..GRID {
+                for j in 0..GRID {
+                    let (u, v) = ((i as f64 + 0.5) / GRID as f64, (j as f64 + 0.5) / GRID as f64);
+                    sum = sum + texture.value(u, v, center);
+                }
+            }
+            sum.scalar_div((GRID * GRID) as f64)
+        }
+    };
+    Some(radiance.x().max(radiance.y()).max(radiance.z()))
+}
+
+// The power and orientation light samplers use to choose between
+// emitters, from the area and normal of a sampled surface point. Scene
+// emitters are two-sided, so flat ones emit pi * L * A from each side.
+fn surface_light_bounds(shape: &Arc<dyn Hittable>, radiance: f64, planar: bool) -> Option<LightBounds> {
+    let (rec, pdf) = shape.sample_surface()?;
+    let area = 1.0 / pdf;
+    let bbox = shape.bounding_box();
+    Some(if planar {
+        LightBounds::planar(bbox, 2.0 * PI * radiance * area, rec.normal, true)
+    } else {
+        LightBounds::omnidirectional(bbox, PI * radiance * area)
+    })
+}
+
+// Whether the linear part of `m` is a rotation times a uniform scale.
+fn is_similarity(m: &Mat4) -> bool {
+    let column = |j: usize| Vec3::new(m.m[0][j], m.m[1][j], m.m[2][j]);
+    let (x, y, z) = (column(0), column(1), column(2));
+    let scale = x.length_squared();
+    let close = |a: f64, b: f64| (a - b).abs() <= 1e-9 * scale;
+    close(y.length_squared(), scale)
+        && close(z.length_squared(), scale)
+        && close(Vec3::dot_product(x, y), 0.0)
+        && close(Vec3::dot_product(y, z), 0.0)
+        && close(Vec3::dot_product(z, x), 0.0)
+}
+
+// The shape an object wraps, if any.
+fn shape_dependency(object: &ObjectDesc) -> Option<&String> {
+    match object {
+        ObjectDesc::ConstantMedium { boundary, .. } => Some(boundary),
+        ObjectDesc::Instance { shape, .. } => Some(shape),
+        _ => None,
+    }
+}
+
+fn build_object(
+    context: &str,
+    object: &ObjectDesc,
+    materials: &HashMap<&str, Arc<dyn Material>>,
+    shapes: &HashMap<&str, Arc<dyn Hittable>>,
+    base_dir: &Path,
+    warnings: &mut Vec<String>,
+) -> Result<Arc<dyn Hittable>, SceneError> {
+    let lookup = |name: &String| {
+        materials.get(name.as_str()).cloned()
+            .ok_or_else(|| SceneError::UnknownMaterial { context: context.to_string(), name: name.clone() })
+    };
+
+    let find_shape = |name: &String| {
+        shapes.get(name.as_str()).cloned()
+            .ok_or_else(|| invalid(context.to_string(), &format!("unknown shape '{}'", name)))
+    };
+
+    Ok(match object {
+        ObjectDesc::Sphere { center, center_end, radius, material } => {
+            if *radius <= 0.0 {
+                return Err(invalid(context.to_string(), "radius must be positive"))
+            }
+            let center_end = center_end.as_ref().unwrap_or(center);
+            Arc::new(Sphere::moving(to_vec3(center), to_vec3(center_end), *radius, lookup(material)?))
+        }
+        ObjectDesc::Triangle { vertices, material } => {
+            let [a, b, c] = vertices.map(|v| to_vec3(&v));
+            if Vec3::cross_product(b - a, c - a).near_zero() {
+                return Err(invalid(context.to_string(), "triangle is degenerate"))
+            }
+            Arc::new(Triangle::new(a, b, c, lookup(material)?))
+        }
+        ObjectDesc::Quad { corner, u, v, material } | ObjectDesc::Disk { center: corner, u, v, material } => {
+            let (u, v) = (to_vec3(u), to_vec3(v));
+            if Vec3::cross_product(u, v).near_zero() {
+                return Err(invalid(context.to_string(), "u and v must not be parallel"))
+            }
+            let shape = if matches!(object, ObjectDesc::Disk { .. }) { PlanarShape::Disk } else { PlanarShape::Parallelogram };
+            Arc::new(Quad::with_shape(to_vec3(corner), u, v, shape, lookup(material)?))
+        }
+        ObjectDesc::Plane { point, normal, uv_scale, material } => {
+            let normal = to_vec3(normal);
+            if normal.near_zero() {
+                return Err(invalid(context.to_string(), "normal must not be zero"))
+            }
+            if *uv_scale <= 0.0 {
+                return Err(invalid(context.to_string(), "uv_scale must be positive"))
+            }
+            Arc::new(Plane::with_uv_scale(to_vec3(point), normal, *uv_scale, lookup(material)?))
+        }
+        ObjectDesc::Box { min, max, material } => {
+            let (a, b) = (to_vec3(min), to_vec3(max));
+            if (0..3).any(|axis| a.e[axis] >= b.e[axis]) {
+                return Err(invalid(context.to_string(), "max must be greater than min on every axis"))
+            }
+            Arc::new(make_box(a, b, lookup(material)?))
+        }
+        ObjectDesc::Obj { path } => {
+            let model = load_obj(&base_dir.join(path))?;
+            warnings.extend(model.warnings.into_iter().map(|warning| format!("{}: {}", context, warning)));
+            Arc::new(model.meshes)
+        }
+        ObjectDesc::ConstantMedium { boundary, density, material } => {
+            if *density <= 0.0 {
+                return Err(invalid(context.to_string(), "density must be positive"))
+            }
+            Arc::new(ConstantMedium::with_phase_function(find_shape(boundary)?, *density, lookup(material)?))
+        }
+        ObjectDesc::Instance { shape, scale, rotate, translate, matrix } => {
+            let object = find_shape(shape)?;
+            let to_world = instance_matrix(context, *scale, *rotate, *translate, *matrix)?;
+            match Transform::new(object, to_world) {
+                Some(transform) => Arc::new(transform),
+                None => return Err(invalid(context.to_string(), "transform is not invertible")),
+            }
+        }
+    })
+}
+
+// The object-to-world matrix of an `instance` object.
+fn instance_matrix(
+    context: &str,
+    scale: Option<[f64; 3]>,
+    rotate: Option<[f64; 3]>,
+    translate: Option<[f64; 3]>,
+    matrix: Option<[[f64; 4]; 4]>,
+) -> Result<Mat4, SceneError> {
+    Ok(match matrix {
+        Some(m) => {
+            if scale.is_some() || rotate.is_some() || translate.is_some() {
+                return Err(invalid(context.to_string(), "matrix cannot be combined with scale, rotate or translate"))
+            }
+            Mat4::new(m)
+        }
+        None => {
+            let mut m = Mat4::scaling(to_vec3(&scale.unwrap_or([1.0, 1.0, 1.0])));
+            for (axis, angle) in rotate.unwrap_or([0.0, 0.0, 0.0]).into_iter().enumerate() {
+                m = Mat4::mul(&Mat4::rotation(axis, angle), &m);
+            }
+            Mat4::mul(&Mat4::translation(to_vec3(&translate.unwrap_or([0.0, 0.0, 0.0]))), &m)
+        }
+    })
+}
+
+fn build_camera(desc: &CameraDesc) -> Result<Camera, SceneError> {
+    let check = |ok: bool, message: &str| if ok { Ok(()) } else { Err(invalid("camera".to_string(), message)) };
+    check(desc.aspect_ratio > 0.0, "aspect_ratio must be positive")?;
+    check(desc.image_width > 0, "image_width must be at least 1")?;
+    check(desc.samples_per_pixel > 0, "samples_per_pixel must be at least 1")?;
+    check(desc.max_depth > 0, "max_depth must be at least 1")?;
+    check(desc.vfov > 0 && desc.vfov < 180, "vfov must be between 0 and 180 degrees")?;
+    check(desc.defocus_angle >= 0.0, "defocus_angle must not be negative")?;
+    check(desc.focus_dist > 0.0, "focus_dist must be positive")?;
+    check(desc.shutter_close >= desc.shutter_open, "shutter_close must not be before shutter_open")?;
+
+    let lookfrom = to_vec3(&desc.lookfrom);
+    let lookat = to_vec3(&desc.lookat);
+    let vup = to_vec3(&desc.vup);
+    let view = lookat - lookfrom;
+    check(!view.near_zero(), "lookfrom and lookat must differ")?;
+    check(!Vec3::cross_product(view, vup).near_zero(), "vup must not be parallel to the view direction")?;
+
+    let mut camera = Camera::new(CameraSettings {
+        aspect_ratio: desc.aspect_ratio,
+        image_width: desc.image_width,
+        samples_per_pixel: desc.samples_per_pixel,
+        max_depth: desc.max_depth,
+        vfov: desc.vfov,
+        lookfrom,
+        lookat,
+        vup,
+        defocus_angle: desc.defocus_angle,
+        focus_dist: desc.focus_dist,
+    });
+    camera.shutter_open = desc.shutter_open;
+    camera.shutter_close = desc.shutter_close;
+    Ok(camera)
+}
+
+fn build_texture(name: &str, desc: &TextureDesc, base_dir: &Path) -> Result<Arc<dyn Texture>, SceneError> {
+    let context = || format!("textures.{}", name);
+    Ok(match desc {
+        TextureDesc::Solid { color } => Arc::new(SolidColor::new(to_vec3(color))),
+        TextureDesc::Checker { mapping, scale, even, odd } => {
+            if *scale <= 0.0 {
+                return Err(invalid(context(), "scale must be positive"))
+            }
+            let mapping = match mapping {
+                CheckerMappingDesc::Spatial => CheckerMapping::Spatial,
+                CheckerMappingDesc::Uv => CheckerMapping::Uv,
+            };
+            Arc::new(CheckerTexture::from_colors(mapping, *scale, to_vec3(even), to_vec3(odd)))
+        }
+        TextureDesc::Image { path, wrap, filter, linear } => {
+            let wrap = match wrap {
+                WrapModeDesc::Repeat => WrapMode::Repeat,
+                WrapModeDesc::MirroredRepeat => WrapMode::MirroredRepeat,
+                WrapModeDesc::Clamp => WrapMode::Clamp,
+            };
+            let filter = match filter {
+                FilterDesc::Nearest => Filter::Nearest,
+                FilterDesc::Bilinear => Filter::Bilinear,
+            };
+            let path = base_dir.join(path);
+            let open = if *linear { ImageTexture::open_linear } else { ImageTexture::open };
+            let texture = open(&path, wrap, filter)
+                .map_err(|err| SceneError::Texture { path: path.clone(), message: err.to_string() })?;
+            Arc::new(texture)
+        }
+        TextureDesc::Noise { pattern, frequency, octaves, seed, color_a, color_b } => {
+            if *frequency <= 0.0 {
+                return Err(invalid(context(), "frequency must be positive"))
+            }
+            if *octaves == 0 || *octaves > 16 {
+                return Err(invalid(context(), "octaves must be between 1 and 16"))
+            }
+            let pattern = match pattern {
+                NoisePatternDesc::Noise => NoisePattern::Noise,
+                NoisePatternDesc::Turbulence => NoisePattern::Turbulence,
+                NoisePatternDesc::Fbm => NoisePattern::Fbm,
+                NoisePatternDesc::Marble => NoisePattern::Marble,
+                NoisePatternDesc::Wood => NoisePattern::Wood,
+            };
+            Arc::new(NoiseTexture::with_colors(pattern, *frequency, *octaves, *seed, to_vec3(color_a), to_vec3(color_b)))
+        }
+    })
+}
+
+fn build_material(name: &str, desc: &MaterialDesc, textures: &HashMap<&str, Arc<dyn Texture>>) -> Result<Arc<dyn Material>, SceneError> {
+    let context = || format!("materials.{}", name);
+    let texture = |texture: &String| -> Result<Arc<dyn Texture>, SceneError> {
+        textures.get(texture.as_str()).cloned().ok_or_else(|| {
+            SceneError::UnknownTexture { material: name.to_string(), name: texture.clone() }
+        })
+    };
+    let input = |input: &ColorInput| -> Result<Arc<dyn Texture>, SceneError> {
+        match input {
+            ColorInput::Color(color) => Ok(Arc::new(SolidColor::new(to_vec3(color)))),
+            ColorInput::Texture(name) => texture(name),
+        }
+    };
+    let scalar = |input: &ScalarInput, field: &str| -> Result<Arc<dyn Texture>, SceneError> {
+        match input {
+            ScalarInput::Value(x) if !(0.0..=1.0).contains(x) => {
+                Err(invalid(context(), &format!("{} must be between 0 and 1", field)))
+            }
+            ScalarInput::Value(x) => Ok(Arc::new(SolidColor::new(Vec3::new(*x, *x, *x)))),
+            ScalarInput::Texture(name) => texture(name),
+        }
+    };
+
+    Ok(match desc {
+        MaterialDesc::Lambertian { albedo } => Arc::new(Lambertian::from_texture(input(albedo)?)),
+        MaterialDesc::Metal { albedo, fuzz } => {
+            if !(0.0..=1.0).contains(fuzz) {
+                return Err(invalid(context(), "fuzz must be between 0 and 1"))
+            }
+            Arc::new(Metal::from_texture(input(albedo)?, *fuzz))
+        }
+        MaterialDesc::Dielectric { refraction_index } => {
+            if *refraction_index <= 0.0 {
+                return Err(invalid(context(), "refraction_index must be positive"))
+            }
+            Arc::new(Dielectric::new(*refraction_index))
+        }
+        MaterialDesc::DiffuseLight { emit } => {
+            if let ColorInput::Color(color) = emit && color.iter().any(|c| *c < 0.0) {
+                return Err(invalid(context(), "emit must not be negative"))
+            }
+            Arc::new(DiffuseLight::from_texture(input(emit)?))
+        }
+        MaterialDesc::Isotropic { albedo } => Arc::new(Isotropic::from_texture(input(albedo)?)),
+        MaterialDesc::Pbr { base_color, metallic, roughness, eta, k } => {
+            let pbr = Pbr::from_textures(input(base_color)?, scalar(metallic, "metallic")?, scalar(roughness, "roughness")?);
+            match (eta, k) {
+                (None, None) => Arc::new(pbr),
+                (Some(eta), Some(k)) => {
+                    if eta.iter().any(|x| *x <= 0.0) {
+                        return Err(invalid(context(), "eta must be positive"))
+                    }
+                    if k.iter().any(|x| *x < 0.0) {
+                        return Err(invalid(context(), "k must not be negative"))
+                    }
+                    Arc::new(pbr.with_conductor(Conductor::new(to_vec3(eta), to_vec3(k))))
+                }
+                _ => return Err(invalid(context(), "eta and k must be given together")),
+            }
+        }
+    })
+}
+
+fn invalid(context: String, message: &str) -> SceneError {
+    SceneError::Invalid { context, message: message.to_string() }
+}
+
+fn to_vec3(v: &[f64; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: &str = r#"
+[materials.white]
+type = "lambertian"
+albedo = [0.7, 0.7, 0.7]
+"#;
+
+    fn parse(source: &str) -> Result<Scene, SceneError> {
+        parse_scene(source, Path::new("test.toml"))
+    }
+
+    #[test]
+    fn invalid_scenes_name_the_problem() {
+        let cases = [
+            (r#"
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, -1.0]
+radius = 0.5
+material = "missing"
+"#, "objects[0]: unknown material 'missing'"),
+            (r#"
+[materials.m]
+type = "lambertian"
+albedo = "missing"
+"#, "materials.m: unknown texture 'missing'"),
+            (r#"
+[camera]
+focus_dist = 0.0
+"#, "camera: focus_dist must be positive"),
+            (r#"
+[camera]
+vfov = 180
+"#, "camera: vfov must be between 0 and 180 degrees"),
+            (r#"
+[materials.m]
+type = "metal"
+albedo = [1.0, 1.0, 1.0]
+fuzz = 2.0
+"#, "materials.m: fuzz must be between 0 and 1"),
+            (r#"
+[materials.m]
+type = "pbr"
+base_color = [1.0, 1.0, 1.0]
+eta = [0.2, 0.9, 1.1]
+"#, "materials.m: eta and k must be given together"),
+            (r#"
+[[objects]]
+type = "instance"
+shape = "missing"
+"#, "objects[0]: unknown shape 'missing'"),
+            (r#"
+[shapes.a]
+type = "instance"
+shape = "missing"
+"#, "shapes.a: unknown shape 'missing'"),
+            (r#"
+[shapes.a]
+type = "instance"
+shape = "b"
+
+[shapes.b]
+type = "instance"
+shape = "a"
+"#, "shapes.a: shapes refer to each other in a cycle 'b'"),
+        ];
+        for (source, expected) in cases {
+            match parse(source) {
+                Ok(_) => panic!("expected an error from:{}", source),
+                Err(err) => assert_eq!(err.to_string(), expected),
+            }
+        }
+
+        let sphere = r#"
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, -1.0]
+radius = -1.0
+material = "white"
+"#;
+        let err = parse(&format!("{}{}", WHITE, sphere)).err().unwrap();
+        assert_eq!(err.to_string(), "objects[0]: radius must be positive");
+    }
+
+    #[test]
+    fn syntax_errors_name_the_file() {
+        let message = parse("[camera\n").err().unwrap().to_string();
+        assert!(message.starts_with("test.toml: "), "{}", message);
+    }
+
+    #[test]
+    fn shapes_resolve_in_dependency_order() {
+        // `a` wraps `b`, which sorts after it, so `b` must be built first.
+        let shapes = r#"
+[shapes.a]
+type = "instance"
+shape = "b"
+scale = [2.0, 2.0, 2.0]
+
+[shapes.b]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "white"
+
+[[objects]]
+type = "instance"
+shape = "a"
+translate = [0.0, 0.0, -5.0]
+"#;
+        let scene = parse(&format!("{}{}", WHITE, shapes)).unwrap();
+        let bbox = scene.world.bounding_box();
+        assert!((bbox.z.min + 7.0).abs() < 1e-9 && (bbox.z.max + 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn loads_the_cornell_box() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/cornell_box.toml");
+        let scene = load_scene(&path).unwrap();
+        assert_eq!(scene.camera.image_width, 600);
+        assert_eq!(scene.camera.vfov, 40);
+        assert!(!scene.world.objects.is_empty());
+        // The ceiling light is picked up as an area light.
+        assert!(!scene.lights.is_empty());
+        assert!(scene.warnings.is_empty(), "{:?}", scene.warnings);
+    }
+}
@@ -0,0 +1,188 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+use crate::light::{Light, LightSample};
+use crate::onb::Onb;
+use crate::random_double;
+use crate::vec3::{Point3, Vec3};
+
+// Solar illuminance outside the atmosphere divided by the solid angle of the
+// sun, in kcd/m² (the unit of the sky model's luminance).
+const SUN_LUMINANCE: f64 = 1.88e6;
+
+// Preetham et al. (1999) analytic daylight sky for a given sun position and
+// atmospheric turbidity, with the sun itself as a small bright disk.
+// Radiance is in kcd/m² times `intensity`. Below the horizon the horizon
+// colour is continued; scenes are expected to have a ground.
+pub struct Sky{
+    pub turbidity: f64,
+    pub intensity: f64,
+    pub sun: Sun,
+    theta_sun: f64,
+    zenith: [f64; 3],
+    perez: [[f64; 5]; 3],
+}
+
+// The sun as seen through the atmosphere: a uniform disk of the given
+// angular radius, sampled as a light. Shadow rays that escape towards it see
+// its radiance through `Background::Sky`.
+#[derive(Clone, Copy, Debug)]
+pub struct Sun{
+    pub direction: Vec3,
+    pub angular_radius: f64,
+    pub radiance: Vec3,
+    cos_angular_radius: f64,
+}
+
+impl Sky{
+    // Elevation and azimuth are in degrees. Azimuth 0 puts the sun towards
+    // -Z and 90 towards +X. Turbidity runs from about 2 (very clear) to 10
+    // (hazy).
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64, intensity: f64) -> Sky {
+        Sky::with_sun_size(elevation, azimuth, turbidity, intensity, 0.53)
+    }
+
+    // As `new`, with the sun's angular diameter in degrees.
+    pub fn with_sun_size(elevation: f64, azimuth: f64, turbidity: f64, intensity: f64, sun_diameter: f64) -> Sky {
+        let (el, az) = (crate::degrees_to_radians(elevation), crate::degrees_to_radians(azimuth));
+        let direction = Vec3::new(el.cos() * az.sin(), el.sin(), -el.cos() * az.cos());
+        let theta_sun = (PI / 2.0 - el).clamp(0.0, PI / 2.0);
+        let t = turbidity;
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let (th, th2, th3) = (theta_sun, theta_sun * theta_sun, theta_sun * theta_sun * theta_sun);
+        let zenith_x = t * t * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let zenith_y = t * t * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+        // Perez distribution coefficients A..E for luminance Y and
+        // chromaticity x and y.
+        let perez = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+
+        let angular_radius = crate::degrees_to_radians(sun_diameter / 2.0);
+        // A sun below the horizon gives no direct light.
+        let radiance = if elevation > 0.0 {
+            sun_transmittance(theta_sun, turbidity).scalar_mul(SUN_LUMINANCE * intensity)
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        };
+        let sun = Sun { direction, angular_radius, radiance, cos_angular_radius: angular_radius.cos() };
+
+        Sky {
+            turbidity,
+            intensity,
+            sun,
+            theta_sun,
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+            perez,
+        }
+    }
+
+    pub fn value(&self, direction: Vec3) -> Vec3 {
+        let d = direction.unit_vector();
+        let sky = self.sky_radiance(d);
+        if self.sun.contains(d) {
+            sky + self.sun.radiance
+        } else {
+            sky
+        }
+    }
+
+    fn sky_radiance(&self, d: Vec3) -> Vec3 {
+        // Keep cos(theta) off zero so the Perez term stays finite at the
+        // horizon.
+        let cos_theta = d.y().max(0.01);
+        let cos_gamma = Vec3::dot_product(d, self.sun.direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        let mut ratio = [0.0; 3];
+        for (k, c) in self.perez.iter().enumerate() {
+            let f = perez(c, cos_theta, gamma, cos_gamma);
+            let f0 = perez(c, 1.0, self.theta_sun, self.theta_sun.cos());
+            ratio[k] = self.zenith[k] * f / f0;
+        }
+        let [luminance, x, y] = ratio;
+        xyy_to_rgb(x, y, luminance).scalar_mul(self.intensity)
+    }
+}
+
+impl fmt::Debug for Sky {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sky")
+            .field("turbidity", &self.turbidity)
+            .field("intensity", &self.intensity)
+            .field("sun", &self.sun)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Sun{
+    pub fn contains(&self, direction: Vec3) -> bool {
+        Vec3::dot_product(direction.unit_vector(), self.direction) >= self.cos_angular_radius
+    }
+
+    fn random(&self) -> Vec3 {
+        let z = 1.0 + random_double() * (self.cos_angular_radius - 1.0);
+        let phi = 2.0 * PI * random_double();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        Onb::new(self.direction).transform(Vec3::new(r * phi.cos(), r * phi.sin(), z))
+    }
+}
+
+impl Light for Sun{
+    fn sample(&self, origin: Point3) -> Option<LightSample> {
+        let direction = self.random();
+        Some(LightSample::Emitter { direction, pdf: self.pdf_value(origin, direction) })
+    }
+
+    fn pdf_value(&self, _origin: Point3, direction: Vec3) -> f64 {
+        if self.contains(direction) {
+            1.0 / (2.0 * PI * (1.0 - self.cos_angular_radius))
+        } else {
+            0.0
+        }
+    }
+}
+
+fn perez(c: &[f64; 5], cos_theta: f64, gamma: f64, cos_gamma: f64) -> f64 {
+    (1.0 + c[0] * (c[1] / cos_theta).exp()) * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+}
+
+// CIE xyY to linear sRGB (D65).
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Vec3 {
+    if y <= 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0)
+    }
+    let cx = x * luminance / y;
+    let cy = luminance;
+    let cz = (1.0 - x - y) * luminance / y;
+    Vec3::new(
+        (3.2406 * cx - 1.5372 * cy - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * cy + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * cy + 1.0570 * cz).max(0.0),
+    )
+}
+
+// Fraction of sunlight reaching the ground through Rayleigh and aerosol
+// scattering, from the appendix of the Preetham paper, evaluated at
+// representative red, green and blue wavelengths (in micrometres).
+fn sun_transmittance(theta_sun: f64, turbidity: f64) -> Vec3 {
+    let theta_degrees = theta_sun.to_degrees();
+    let air_mass = 1.0 / (theta_sun.cos() + 0.15 * (93.885 - theta_degrees).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+
+    let channel = |lambda: f64| {
+        let rayleigh = (-air_mass * 0.008735 * lambda.powf(-4.08)).exp();
+        let aerosol = (-air_mass * beta * lambda.powf(-1.3)).exp();
+        rayleigh * aerosol
+    };
+    Vec3::new(channel(0.680), channel(0.550), channel(0.440))
+}
@@ -1,30 +1,62 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::material::{Material};
 use crate::vec3::*;
+use crate::onb::Onb;
+use crate::random_double;
 use crate::ray::Ray;
 
 pub struct Sphere{
+    // Center at time 0; a moving sphere travels by `velocity` per unit time.
     pub center : Point3,
+    pub velocity : Vec3,
     pub radius : f64,
     pub mat: Arc<dyn Material>,
+    bbox: Aabb,
 }
 
 impl Sphere{
     pub fn new(center : Point3, radius : f64, mat: Arc<dyn Material>) -> Sphere{
+        Sphere::moving(center, center, radius, mat)
+    }
+
+    // A sphere whose center moves linearly from `center1` at time 0 to
+    // `center2` at time 1.
+    pub fn moving(center1 : Point3, center2 : Point3, radius : f64, mat: Arc<dyn Material>) -> Sphere{
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        let box1 = Aabb::from_points(center1 - rvec, center1 + rvec);
+        let box2 = Aabb::from_points(center2 - rvec, center2 + rvec);
         Sphere{
-            center,
-            radius : radius.max(0.0),
+            center: center1,
+            velocity: center2 - center1,
+            radius,
             mat,
+            bbox: Aabb::enclosing(&box1, &box2),
         }
     }
+
+    pub fn center_at(&self, time: f64) -> Point3 {
+        self.center + self.velocity.scalar_mul(time)
+    }
+
+    // Maps a point on the unit sphere to (u, v): u is the angle around the
+    // Y axis from X=-1, v the angle from Y=-1 to Y=+1, both scaled to [0, 1].
+    pub fn get_sphere_uv(p: Point3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
 
 impl Hittable for Sphere{
     fn hit (&self,r :&Ray, ray_t: Interval, rec: &mut HitRecord) -> bool{
-        let oc = Vec3::sub(self.center, r.origin());
+        let current_center = self.center_at(r.time());
+        let oc = current_center - r.origin();
         let a = r.direction().length_squared();
         let h = Vec3::dot_product(r.direction(), oc);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -46,10 +78,65 @@ impl Hittable for Sphere{
 
         rec.t = root;
         rec.p = r.ray_at(rec.t);
-        let outer_normal = (Vec3::sub(rec.p, self.center)).scalar_div(self.radius) ;
+        let outer_normal = (rec.p - current_center).scalar_div(self.radius) ;
         rec.set_face_normal(r, outer_normal);
+        (rec.u, rec.v) = Sphere::get_sphere_uv(outer_normal);
         rec.mat = self.mat.clone();
 
         true
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // Samples the cone of directions the sphere subtends. Light sampling has
+    // no ray time, so a moving sphere is sampled where it is at time 0.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0
+        }
+
+        let distance_squared = (self.center - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 1.0 / (4.0 * PI)
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Vec3::random_unit_vector()
+        }
+
+        // Uniform direction inside the cone around +z, then rotated so +z
+        // points at the center.
+        let r1 = random_double();
+        let r2 = random_double();
+        let z = 1.0 + r2 * ((1.0 - self.radius * self.radius / distance_squared).sqrt() - 1.0);
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * (1.0 - z * z).sqrt();
+        let y = phi.sin() * (1.0 - z * z).sqrt();
+
+        Onb::new(direction).transform(Vec3::new(x, y, z))
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        if self.radius <= 0.0 {
+            return None
+        }
+        let outward_normal = Vec3::random_unit_vector();
+        let mut rec = HitRecord::new();
+        rec.p = self.center + outward_normal.scalar_mul(self.radius);
+        rec.normal = outward_normal;
+        rec.front_face = true;
+        (rec.u, rec.v) = Sphere::get_sphere_uv(outward_normal);
+        rec.mat = self.mat.clone();
+        Some((rec, 1.0 / (4.0 * PI * self.radius * self.radius)))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,227 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::color::gamma_to_linear;
+use crate::interval::Interval;
+use crate::perlin::Perlin;
+use crate::vec3::{Point3, Vec3};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Vec3;
+}
+
+pub struct SolidColor{
+    pub albedo: Vec3,
+}
+
+impl SolidColor{
+    pub fn new(albedo: Vec3) -> SolidColor {
+        SolidColor { albedo }
+    }
+}
+
+impl Texture for SolidColor{
+    fn value(&self, _u: f64, _v: f64, _p: Point3) -> Vec3 {
+        self.albedo
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckerMapping {
+    // Cubes of side `scale` in world space.
+    Spatial,
+    // Squares of side `scale` in (u, v) space.
+    Uv,
+}
+
+pub struct CheckerTexture{
+    pub mapping: CheckerMapping,
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+    inv_scale: f64,
+}
+
+impl CheckerTexture{
+    pub fn new(mapping: CheckerMapping, scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> CheckerTexture {
+        CheckerTexture { mapping, even, odd, inv_scale: 1.0 / scale }
+    }
+
+    pub fn from_colors(mapping: CheckerMapping, scale: f64, even: Vec3, odd: Vec3) -> CheckerTexture {
+        CheckerTexture::new(mapping, scale, Arc::new(SolidColor::new(even)), Arc::new(SolidColor::new(odd)))
+    }
+}
+
+impl Texture for CheckerTexture{
+    fn value(&self, u: f64, v: f64, p: Point3) -> Vec3 {
+        let cell = |x: f64| (self.inv_scale * x).floor() as i64;
+        let sum = match self.mapping {
+            CheckerMapping::Spatial => cell(p.x()) + cell(p.y()) + cell(p.z()),
+            CheckerMapping::Uv => cell(u) + cell(v),
+        };
+
+        if sum.rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    Clamp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+// A bitmap sampled with (u, v) in [0, 1]², v = 0 being the bottom row.
+// Texels are stored linear, decoded with the inverse of the output gamma.
+pub struct ImageTexture{
+    pub width: usize,
+    pub height: usize,
+    pub wrap: WrapMode,
+    pub filter: Filter,
+    texels: Vec<Vec3>,
+}
+
+impl ImageTexture{
+    pub fn new(width: usize, height: usize, texels: Vec<Vec3>, wrap: WrapMode, filter: Filter) -> ImageTexture {
+        assert_eq!(texels.len(), width * height, "texel count does not match {}x{}", width, height);
+        ImageTexture { width, height, wrap, filter, texels }
+    }
+
+    // Loads a PNG or JPEG file.
+    pub fn open(path: &Path, wrap: WrapMode, filter: Filter) -> Result<ImageTexture, ::image::ImageError> {
+        ImageTexture::load(path, wrap, filter, gamma_to_linear)
+    }
+
+    // Loads a PNG or JPEG file holding non-colour data, such as a roughness
+    // map, whose values are used as stored.
+    pub fn open_linear(path: &Path, wrap: WrapMode, filter: Filter) -> Result<ImageTexture, ::image::ImageError> {
+        ImageTexture::load(path, wrap, filter, |x| x)
+    }
+
+    fn load(path: &Path, wrap: WrapMode, filter: Filter, decode: fn(f64) -> f64) -> Result<ImageTexture, ::image::ImageError> {
+        let rgb = ::image::open(path)?.into_rgb32f();
+        let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+        let texels = rgb
+            .pixels()
+            .map(|p| Vec3::new(decode(p[0] as f64), decode(p[1] as f64), decode(p[2] as f64)))
+            .collect();
+        Ok(ImageTexture::new(width, height, texels, wrap, filter))
+    }
+
+    fn wrap_coordinate(&self, i: i64, size: usize) -> usize {
+        let n = size as i64;
+        let wrapped = match self.wrap {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::MirroredRepeat => {
+                let m = i.rem_euclid(2 * n);
+                if m < n { m } else { 2 * n - 1 - m }
+            }
+            WrapMode::Clamp => i.clamp(0, n - 1),
+        };
+        wrapped as usize
+    }
+
+    fn texel(&self, i: i64, j: i64) -> Vec3 {
+        let x = self.wrap_coordinate(i, self.width);
+        let y = self.wrap_coordinate(j, self.height);
+        self.texels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture{
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Vec3 {
+        if self.width == 0 || self.height == 0 {
+            return Vec3::new(0.0, 1.0, 1.0)
+        }
+
+        let (u, v) = match self.wrap {
+            WrapMode::Clamp => {
+                let unit = Interval::new(0.0, 1.0);
+                (Interval::clamp(&unit, u), Interval::clamp(&unit, v))
+            }
+            _ => (u, v),
+        };
+
+        // Continuous texel coordinates, with texel centres at half-integers.
+        let x = u * self.width as f64;
+        let y = (1.0 - v) * self.height as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (i, j) = (x.floor() as i64, y.floor() as i64);
+                let (fx, fy) = (x - x.floor(), y - y.floor());
+
+                let top = self.texel(i, j).scalar_mul(1.0 - fx) + self.texel(i + 1, j).scalar_mul(fx);
+                let bottom = self.texel(i, j + 1).scalar_mul(1.0 - fx) + self.texel(i + 1, j + 1).scalar_mul(fx);
+                top.scalar_mul(1.0 - fy) + bottom.scalar_mul(fy)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoisePattern {
+    // Plain gradient noise remapped to [0, 1].
+    Noise,
+    Turbulence,
+    Fbm,
+    // Sine bands along Z, perturbed by turbulence.
+    Marble,
+    // Concentric rings around the Y axis, perturbed by turbulence.
+    Wood,
+}
+
+// Procedural texture blending between `color_a` (pattern value 0) and
+// `color_b` (value 1). `frequency` scales world-space positions and
+// `octaves` sets how many noise layers the fractal patterns sum.
+pub struct NoiseTexture{
+    pub pattern: NoisePattern,
+    pub frequency: f64,
+    pub octaves: u32,
+    pub color_a: Vec3,
+    pub color_b: Vec3,
+    noise: Perlin,
+}
+
+impl NoiseTexture{
+    pub fn new(pattern: NoisePattern, frequency: f64, octaves: u32, seed: u64) -> NoiseTexture {
+        NoiseTexture::with_colors(pattern, frequency, octaves, seed, Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn with_colors(pattern: NoisePattern, frequency: f64, octaves: u32, seed: u64, color_a: Vec3, color_b: Vec3) -> NoiseTexture {
+        NoiseTexture { pattern, frequency, octaves, color_a, color_b, noise: Perlin::new(seed) }
+    }
+
+    fn pattern_value(&self, p: Point3) -> f64 {
+        let sp = p.scalar_mul(self.frequency);
+        match self.pattern {
+            NoisePattern::Noise => 0.5 * (1.0 + self.noise.noise(sp)),
+            NoisePattern::Turbulence => self.noise.turbulence(sp, self.octaves),
+            NoisePattern::Fbm => 0.5 * (1.0 + self.noise.fbm(sp, self.octaves, 2.0, 0.5)),
+            NoisePattern::Marble => 0.5 * (1.0 + (sp.z() + 10.0 * self.noise.turbulence(sp, self.octaves)).sin()),
+            NoisePattern::Wood => {
+                let radius = (sp.x() * sp.x() + sp.z() * sp.z()).sqrt();
+                let rings = radius + 0.5 * self.noise.turbulence(sp, self.octaves);
+                rings - rings.floor()
+            }
+        }
+    }
+}
+
+impl Texture for NoiseTexture{
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Vec3 {
+        let t = Interval::clamp(&Interval::new(0.0, 1.0), self.pattern_value(p));
+        self.color_a.scalar_mul(1.0 - t) + self.color_b.scalar_mul(t)
+    }
+}
@@ -0,0 +1,397 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::mat4::Mat4;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// Instancing wrappers. Each one moves the incoming ray into the object's
+// space, intersects the wrapped object there and moves the hit back out.
+// Ray directions are not renormalized, so `t` means the same thing in both
+// spaces. The wrapped object is shared, so one mesh can be placed many times.
+
+pub struct Translate{
+    pub object : Arc<dyn Hittable>,
+    pub offset : Vec3,
+    bbox : Aabb,
+}
+
+impl Translate{
+    pub fn new(object: Arc<dyn Hittable>, offset: Vec3) -> Translate {
+        let b = object.bounding_box();
+        let shift = |i: Interval, d: f64| Interval::new(i.min + d, i.max + d);
+        let bbox = Aabb::new(shift(b.x, offset.x()), shift(b.y, offset.y()), shift(b.z, offset.z()));
+        Translate { object, offset, bbox }
+    }
+}
+
+impl Hittable for Translate{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let offset_r = Ray::with_time(r.origin() - self.offset, r.direction(), r.time());
+        if !self.object.hit(&offset_r, ray_t, rec) {
+            return false
+        }
+
+        rec.p = rec.p + self.offset;
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(origin - self.offset, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.object.random(origin - self.offset)
+    }
+}
+
+// Rotation by a fixed angle in degrees about one coordinate axis; use the
+// `RotateX`, `RotateY` and `RotateZ` aliases.
+pub struct Rotate<const AXIS: usize>{
+    pub object : Arc<dyn Hittable>,
+    sin_theta : f64,
+    cos_theta : f64,
+    bbox : Aabb,
+}
+
+pub type RotateX = Rotate<0>;
+pub type RotateY = Rotate<1>;
+pub type RotateZ = Rotate<2>;
+
+impl<const AXIS: usize> Rotate<AXIS>{
+    pub fn new(object: Arc<dyn Hittable>, angle: f64) -> Rotate<AXIS> {
+        let (sin_theta, cos_theta) = crate::degrees_to_radians(angle).sin_cos();
+        let mut rotate = Rotate { object, sin_theta, cos_theta, bbox: Aabb::EMPTY };
+        rotate.bbox = transform_bbox(&rotate.object.bounding_box(), |p| rotate.to_world(p));
+        rotate
+    }
+
+    fn rotate(&self, v: Vec3, sin_theta: f64) -> Vec3 {
+        let (i, j) = ((AXIS + 1) % 3, (AXIS + 2) % 3);
+        let mut e = v.e;
+        e[i] = self.cos_theta * v.e[i] - sin_theta * v.e[j];
+        e[j] = sin_theta * v.e[i] + self.cos_theta * v.e[j];
+        Vec3 { e }
+    }
+
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        self.rotate(v, self.sin_theta)
+    }
+
+    fn to_object(&self, v: Vec3) -> Vec3 {
+        self.rotate(v, -self.sin_theta)
+    }
+}
+
+impl<const AXIS: usize> Hittable for Rotate<AXIS>{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let rotated_r = Ray::with_time(self.to_object(r.origin()), self.to_object(r.direction()), r.time());
+        if !self.object.hit(&rotated_r, ray_t, rec) {
+            return false
+        }
+
+        // A rotation is orthonormal, so normals rotate like points.
+        rec.p = self.to_world(rec.p);
+        rec.normal = self.to_world(rec.normal);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // Rotations preserve solid angle, so the object's own density applies.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(self.to_object(origin), self.to_object(direction))
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.to_world(self.object.random(self.to_object(origin)))
+    }
+}
+
+// Per-axis scaling. Negative factors mirror the object.
+pub struct Scale{
+    pub object : Arc<dyn Hittable>,
+    pub factor : Vec3,
+    bbox : Aabb,
+}
+
+impl Scale{
+    // Returns None if any factor is zero.
+    pub fn new(object: Arc<dyn Hittable>, factor: Vec3) -> Option<Scale> {
+        if factor.e.contains(&0.0) {
+            return None
+        }
+        let bbox = transform_bbox(&object.bounding_box(), |p| p * factor);
+        Some(Scale { object, factor, bbox })
+    }
+
+    fn inverse_factor(&self) -> Vec3 {
+        Vec3::new(1.0 / self.factor.x(), 1.0 / self.factor.y(), 1.0 / self.factor.z())
+    }
+}
+
+impl Hittable for Scale{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let inv = self.inverse_factor();
+        let scaled_r = Ray::with_time(r.origin() * inv, r.direction() * inv, r.time());
+        if !self.object.hit(&scaled_r, ray_t, rec) {
+            return false
+        }
+
+        rec.p = rec.p * self.factor;
+        rec.normal = (rec.normal * inv).unit_vector();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let inv = self.inverse_factor();
+        let object_direction = direction * inv;
+        let det = (inv.x() * inv.y() * inv.z()).abs();
+        self.object.pdf_value(origin * inv, object_direction) * solid_angle_jacobian(direction, object_direction, det)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.object.random(origin * self.inverse_factor()) * self.factor
+    }
+}
+
+// General affine transform given as an object-to-world matrix.
+pub struct Transform{
+    pub object : Arc<dyn Hittable>,
+    pub to_world : Mat4,
+    to_object : Mat4,
+    normal_to_world : Mat4,
+    bbox : Aabb,
+}
+
+impl Transform{
+    // Returns None if `to_world` is singular.
+    pub fn new(object: Arc<dyn Hittable>, to_world: Mat4) -> Option<Transform> {
+        let to_object = to_world.inverse()?;
+        let normal_to_world = to_object.transpose();
+        let bbox = transform_bbox(&object.bounding_box(), |p| to_world.transform_point(p));
+        Some(Transform { object, to_world, to_object, normal_to_world, bbox })
+    }
+}
+
+impl Hittable for Transform{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let object_r = Ray::with_time(self.to_object.transform_point(r.origin()), self.to_object.transform_vector(r.direction()), r.time());
+        if !self.object.hit(&object_r, ray_t, rec) {
+            return false
+        }
+
+        // Normals transform by the inverse transpose. That keeps the sign of
+        // dot(normal, direction), so front_face stays valid.
+        rec.p = self.to_world.transform_point(rec.p);
+        rec.normal = self.normal_to_world.transform_vector(rec.normal).unit_vector();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let object_direction = self.to_object.transform_vector(direction);
+        let det = self.to_object.determinant3().abs();
+        self.object.pdf_value(self.to_object.transform_point(origin), object_direction)
+            * solid_angle_jacobian(direction, object_direction, det)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.to_world.transform_vector(self.object.random(self.to_object.transform_point(origin)))
+    }
+
+    // Area stretches by |det| times the length of the inverse-transposed
+    // unit normal, so the density is only uniform if that is the same all
+    // over the surface, as for flat shapes or similarity transforms.
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        let (mut rec, pdf) = self.object.sample_surface()?;
+        let normal = self.normal_to_world.transform_vector(rec.normal);
+        let stretch = self.to_world.determinant3().abs() * normal.length();
+        rec.p = self.to_world.transform_point(rec.p);
+        rec.normal = normal.unit_vector();
+        Some((rec, pdf / stretch))
+    }
+}
+
+// Scale from a solid-angle density in object space to one in world space,
+// for directions mapped by a linear map A with |det A| = `det`: normalising
+// A·ω stretches solid angle around ω by |det A| / |A·ω|³ for unit ω.
+fn solid_angle_jacobian(direction: Vec3, object_direction: Vec3, det: f64) -> f64 {
+    let length = direction.length();
+    if length == 0.0 {
+        return 0.0
+    }
+    let stretch = object_direction.length() / length;
+    det / (stretch * stretch * stretch)
+}
+
+// Box around the eight transformed corners of `bbox`. Unbounded boxes (such
+// as an infinite plane's) can't be transformed corner by corner and become
+// the whole space.
+fn transform_bbox(bbox: &Aabb, transform: impl Fn(Point3) -> Point3) -> Aabb {
+    let axes = [bbox.x, bbox.y, bbox.z];
+    if axes.iter().any(|i| i.min > i.max) {
+        return Aabb::EMPTY
+    }
+    if axes.iter().any(|i| !i.min.is_finite() || !i.max.is_finite()) {
+        return Aabb::UNIVERSE
+    }
+
+    let mut min = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut max = Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for corner in 0..8 {
+        let pick = |axis: usize| if corner & (1 << axis) == 0 { axes[axis].min } else { axes[axis].max };
+        let p = transform(Point3::new(pick(0), pick(1), pick(2)));
+        for axis in 0..3 {
+            min.e[axis] = min.e[axis].min(p.e[axis]);
+            max.e[axis] = max.e[axis].max(p.e[axis]);
+        }
+    }
+    Aabb::from_points(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::material::EmptyMaterial;
+    use crate::onb::Onb;
+    use crate::quad::make_box;
+    use crate::sphere::Sphere;
+    use crate::{random_double_x, seed_thread_rng};
+
+    fn unit_sphere() -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::new(EmptyMaterial)))
+    }
+
+    // Rotation, shear and translation, so normals need the inverse
+    // transpose.
+    fn sheared() -> Mat4 {
+        let mut m = Mat4::mul(&Mat4::rotation(2, 25.0), &Mat4::scaling(Vec3::new(1.5, 0.7, 1.0)));
+        m.m[0][1] += 0.9;
+        m.m[2][0] -= 0.4;
+        Mat4::mul(&Mat4::translation(Vec3::new(0.5, -1.0, 2.0)), &m)
+    }
+
+    // Hits from random rays fired from well outside the box towards points
+    // inside it.
+    fn random_hits(object: &dyn Hittable, count: usize) -> Vec<HitRecord> {
+        let bbox = object.bounding_box();
+        let mut hits = Vec::new();
+        while hits.len() < count {
+            let target = Point3::new(
+                random_double_x(bbox.x.min, bbox.x.max),
+                random_double_x(bbox.y.min, bbox.y.max),
+                random_double_x(bbox.z.min, bbox.z.max),
+            );
+            let origin = target + Vec3::random_unit_vector().scalar_mul(20.0);
+            let mut rec = HitRecord::new();
+            if object.hit(&Ray::new(origin, target - origin), Interval::new(0.001, f64::INFINITY), &mut rec) {
+                hits.push(rec);
+            }
+        }
+        hits
+    }
+
+    // Checks normals of a transformed unit sphere against its tangent plane
+    // carried to world space by the linear part of the transform.
+    fn assert_normals_perpendicular(object: &dyn Hittable, to_object: impl Fn(Point3) -> Point3, linear: impl Fn(Vec3) -> Vec3) {
+        for rec in random_hits(object, 500) {
+            let q = to_object(rec.p);
+            assert!((q.length() - 1.0).abs() < 1e-9, "hit {:?} is off the surface", rec.p);
+            let tangents = Onb::new(q);
+            for tangent in [tangents.u, tangents.v] {
+                let world = linear(tangent).unit_vector();
+                assert!(Vec3::dot_product(rec.normal, world).abs() < 1e-9);
+            }
+            assert!((rec.normal.length() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn scaled_and_sheared_normals_are_perpendicular() {
+        seed_thread_rng(6);
+        let factor = Vec3::new(3.0, 1.0, 0.5);
+        let scale = Scale::new(unit_sphere(), factor).unwrap();
+        let inv = scale.inverse_factor();
+        assert_normals_perpendicular(&scale, |p| p * inv, |v| v * factor);
+
+        let m = sheared();
+        let transform = Transform::new(unit_sphere(), m).unwrap();
+        let inverse = m.inverse().unwrap();
+        assert_normals_perpendicular(&transform, |p| inverse.transform_point(p), |v| m.transform_vector(v));
+    }
+
+    #[test]
+    fn translate_and_rotate_move_hits() {
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Point3::new(2.0, 0.0, 0.0), 1.0, Arc::new(EmptyMaterial)));
+        // A quarter turn about y takes +x to -z.
+        let rotated = Translate::new(Arc::new(RotateY::new(sphere, 90.0)), Vec3::new(0.0, 1.0, 0.0));
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(rotated.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 1.0).abs() < 1e-9);
+        assert!((rec.p - Point3::new(0.0, 1.0, -1.0)).near_zero());
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).near_zero());
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn boxes_contain_the_transformed_geometry() {
+        seed_thread_rng(7);
+        let cube: Arc<dyn Hittable> = Arc::new(make_box(Point3::new(-1.0, -0.5, 0.0), Point3::new(1.0, 0.5, 2.0), Arc::new(EmptyMaterial)));
+        let objects: Vec<Arc<dyn Hittable>> = vec![
+            Arc::new(Translate::new(cube.clone(), Vec3::new(3.0, -1.0, 0.5))),
+            Arc::new(RotateX::new(cube.clone(), 30.0)),
+            Arc::new(RotateY::new(cube.clone(), -50.0)),
+            Arc::new(RotateZ::new(cube.clone(), 120.0)),
+            Arc::new(Scale::new(cube.clone(), Vec3::new(-2.0, 0.5, 3.0)).unwrap()),
+            Arc::new(Transform::new(cube, sheared()).unwrap()),
+            Arc::new(Transform::new(unit_sphere(), sheared()).unwrap()),
+        ];
+        for object in objects {
+            let bbox = object.bounding_box();
+            for rec in random_hits(object.as_ref(), 300) {
+                for axis in 0..3 {
+                    assert!(bbox.axis_interval(axis).expand(1e-9).contains(rec.p.e[axis]), "{:?} outside the box", rec.p);
+                }
+            }
+        }
+    }
+
+    // Mean of pdf_value over uniform directions, times 4 pi.
+    fn pdf_integral(object: &dyn Hittable, origin: Point3) -> f64 {
+        let n = 200_000;
+        let sum: f64 = (0..n).map(|_| object.pdf_value(origin, Vec3::random_unit_vector())).sum();
+        sum / n as f64 * 4.0 * PI
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        seed_thread_rng(8);
+        let scale = Scale::new(unit_sphere(), Vec3::new(3.0, 1.0, 0.5)).unwrap();
+        let integral = pdf_integral(&scale, Point3::new(1.0, 2.0, 0.5));
+        assert!((integral - 1.0).abs() < 0.03, "integral {}", integral);
+
+        let m = sheared();
+        let transform = Transform::new(unit_sphere(), m).unwrap();
+        let integral = pdf_integral(&transform, m.transform_point(Point3::new(0.3, 0.2, 1.8)));
+        assert!((integral - 1.0).abs() < 0.03, "integral {}", integral);
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{area_pdf_to_solid_angle, HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::random_double;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+pub struct Triangle{
+    pub a : Point3,
+    pub b : Point3,
+    pub c : Point3,
+    pub mat: Arc<dyn Material>,
+    normal: Vec3,
+    area: f64,
+    bbox: Aabb,
+}
+
+impl Triangle{
+    pub fn new(a: Point3, b: Point3, c: Point3, mat: Arc<dyn Material>) -> Triangle {
+        let n = Vec3::cross_product(b - a, c - a);
+        Triangle {
+            a,
+            b,
+            c,
+            mat,
+            normal: n.unit_vector(),
+            area: 0.5 * n.length(),
+            bbox: triangle_bbox(a, b, c),
+        }
+    }
+}
+
+impl Hittable for Triangle{
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let Some((t, b1, b2)) = intersect_triangle(self.a, self.b, self.c, r, ray_t) else {
+            return false
+        };
+
+        rec.t = t;
+        rec.p = r.ray_at(t);
+        rec.u = b1;
+        rec.v = b2;
+        rec.set_face_normal(r, self.normal);
+        rec.mat = self.mat.clone();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let r = Ray::new(origin, direction);
+        let Some((t, _, _)) = intersect_triangle(self.a, self.b, self.c, &r, Interval::new(0.001, f64::INFINITY)) else {
+            return 0.0
+        };
+        area_pdf_to_solid_angle(direction, t, self.normal, self.area)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let (b1, b2) = random_barycentric();
+        self.point_at(b1, b2) - origin
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        let (b1, b2) = random_barycentric();
+        let mut rec = HitRecord::new();
+        rec.p = self.point_at(b1, b2);
+        rec.u = b1;
+        rec.v = b2;
+        rec.normal = self.normal;
+        rec.front_face = true;
+        rec.mat = self.mat.clone();
+        Some((rec, 1.0 / self.area))
+    }
+}
+
+impl Triangle{
+    fn point_at(&self, b1: f64, b2: f64) -> Point3 {
+        self.a + ((self.b - self.a).scalar_mul(b1) + (self.c - self.a).scalar_mul(b2))
+    }
+}
+
+// Uniform barycentric weights of `b` and `c`.
+fn random_barycentric() -> (f64, f64) {
+    let (b1, b2) = (random_double(), random_double());
+    if b1 + b2 > 1.0 { (1.0 - b1, 1.0 - b2) } else { (b1, b2) }
+}
+
+pub(crate) fn triangle_bbox(a: Point3, b: Point3, c: Point3) -> Aabb {
+    Aabb::enclosing(&Aabb::from_points(a, b), &Aabb::from_points(a, c))
+}
+
+// Watertight ray/triangle test (Woop, Benthin and Wald, 2013): the vertices
+// are moved into a space where the ray starts at the origin and points down
+// +z, so the three 2D edge functions are evaluated consistently for edges
+// shared by neighbouring triangles and rays can't slip between them. Returns
+// the ray parameter and the barycentric weights of `b` and `c`.
+pub(crate) fn intersect_triangle(a: Point3, b: Point3, c: Point3, r: &Ray, ray_t: Interval) -> Option<(f64, f64, f64)> {
+    let d = r.direction();
+
+    // Permute so the largest direction component becomes z, swapping x and
+    // y when that flips the winding.
+    let kz = if d.x().abs() > d.y().abs() {
+        if d.x().abs() > d.z().abs() { 0 } else { 2 }
+    } else if d.y().abs() > d.z().abs() { 1 } else { 2 };
+    if d.e[kz] == 0.0 {
+        return None
+    }
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if d.e[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // Shear so the ray direction becomes (0, 0, 1).
+    let sx = d.e[kx] / d.e[kz];
+    let sy = d.e[ky] / d.e[kz];
+    let sz = 1.0 / d.e[kz];
+
+    let pa = a - r.origin();
+    let pb = b - r.origin();
+    let pc = c - r.origin();
+    let (ax, ay) = (pa.e[kx] - sx * pa.e[kz], pa.e[ky] - sy * pa.e[kz]);
+    let (bx, by) = (pb.e[kx] - sx * pb.e[kz], pb.e[ky] - sy * pb.e[kz]);
+    let (cx, cy) = (pc.e[kx] - sx * pc.e[kz], pc.e[ky] - sy * pc.e[kz]);
+
+    let mut u = cx * by - cy * bx;
+    let mut v = ax * cy - ay * cx;
+    let mut w = bx * ay - by * ax;
+
+    // A zero edge function may just be rounding; recompute all three with
+    // an exact product difference before trusting it. This is the
+    // higher-precision fallback the paper does in double from single.
+    if u == 0.0 || v == 0.0 || w == 0.0 {
+        u = difference_of_products(cx, by, cy, bx);
+        v = difference_of_products(ax, cy, ay, cx);
+        w = difference_of_products(bx, ay, by, ax);
+    }
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None
+    }
+    // A ray exactly on an edge shared by two triangles would hit both. As
+    // with the top-left rule in rasterisers, a zero edge function only
+    // counts for edges pointing one way; the neighbours run along their
+    // shared edge in opposite directions, so exactly one claims the hit.
+    let edges = [(u, cx - bx, cy - by), (v, ax - cx, ay - cy), (w, bx - ax, by - ay)];
+    if edges.iter().any(|&(e, dx, dy)| e == 0.0 && !(dy > 0.0 || (dy == 0.0 && dx < 0.0))) {
+        return None
+    }
+    let det = u + v + w;
+    if det == 0.0 {
+        return None
+    }
+
+    let t_scaled = u * sz * pa.e[kz] + v * sz * pb.e[kz] + w * sz * pc.e[kz];
+    let t = t_scaled / det;
+    if !Interval::surrounds(&ray_t, t) {
+        return None
+    }
+
+    Some((t, v / det, w / det))
+}
+
+// a * b - c * d, with the rounding error of c * d recovered by a fused
+// multiply-add (Kahan's algorithm).
+fn difference_of_products(a: f64, b: f64, c: f64, d: f64) -> f64 {
+    let cd = c * d;
+    let error = (-c).mul_add(d, cd);
+    a.mul_add(b, -cd) + error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+    use crate::{random_double_x, seed_thread_rng};
+
+    // The unit square in z = 0 split along its diagonal, both halves wound
+    // counter-clockwise seen from +z.
+    fn split_square() -> [Triangle; 2] {
+        let (a, b, c, d) = (Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0), Point3::new(0.0, 1.0, 0.0));
+        [Triangle::new(a, b, c, Arc::new(EmptyMaterial)), Triangle::new(a, c, d, Arc::new(EmptyMaterial))]
+    }
+
+    fn hit_count(triangles: &[Triangle], r: &Ray) -> usize {
+        triangles.iter().filter(|tri| tri.hit(r, Interval::new(0.001, f64::INFINITY), &mut HitRecord::new())).count()
+    }
+
+    #[test]
+    fn shared_edge_is_hit_once() {
+        seed_thread_rng(5);
+        let triangles = split_square();
+        // Straight down onto the diagonal, where the edge functions are
+        // exactly zero.
+        for k in 1..16 {
+            let x = k as f64 / 16.0;
+            let r = Ray::new(Point3::new(x, x, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            assert_eq!(hit_count(&triangles, &r), 1, "at {}", x);
+        }
+        // From random origins on either side towards points on the diagonal.
+        for _ in 0..10_000 {
+            let x = random_double_x(0.01, 0.99);
+            let origin = Point3::new(random_double_x(-2.0, 3.0), random_double_x(-2.0, 3.0), random_double_x(-3.0, 3.0));
+            if origin.z().abs() < 0.1 {
+                continue
+            }
+            let r = Ray::new(origin, Point3::new(x, x, 0.0) - origin);
+            assert_eq!(hit_count(&triangles, &r), 1, "towards {}", x);
+        }
+    }
+
+    #[test]
+    fn barycentrics_and_faces() {
+        let [tri, _] = split_square();
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(0.75, 0.25, 2.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(tri.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        // p = a + u (b - a) + v (c - a)
+        assert!((rec.u - 0.5).abs() < 1e-12 && (rec.v - 0.25).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).near_zero());
+
+        let r = Ray::new(Point3::new(0.75, 0.25, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(tri.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, -1.0)).near_zero());
+    }
+}
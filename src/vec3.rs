@@ -1,3 +1,5 @@
+use std::ops::{Add, Mul, Sub};
+
 use crate::{random_double, random_double_x};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -28,28 +30,6 @@ impl Vec3 {
         self.e[2]
     }
 
-    #[inline(always)]
-    pub fn add( u:Vec3, v:Vec3 ) -> Vec3{
-        Vec3 {
-            e : [
-                u.e[0] + v.e[0],
-                u.e[1] + v.e[1],
-                u.e[2] + v.e[2],
-            ]
-        }
-    }
-
-    #[inline(always)]
-    pub fn sub( u:Vec3, v:Vec3 ) -> Vec3{
-        Vec3 {
-            e : [
-                u.e[0] - v.e[0],
-                u.e[1] - v.e[1],
-                u.e[2] - v.e[2],
-            ]
-        }
-    }
-
     #[inline(always)]
     pub fn scalar_mul (&self , t : f64) -> Vec3 {
         Vec3 {
@@ -130,7 +110,7 @@ impl Vec3 {
         }
     }
 
-    pub fn _random_on_hemisphere ( normal: Vec3 ) -> Vec3{
+    pub fn random_on_hemisphere ( normal: Vec3 ) -> Vec3{
         let on_unit_sphere = Vec3::random_unit_vector();
         if Vec3::dot_product(on_unit_sphere, normal) > 0.0{
             on_unit_sphere
@@ -147,20 +127,15 @@ impl Vec3 {
 
     #[inline(always)]
     pub fn reflect(v: Vec3, n: Vec3 ) -> Vec3 {
-        return Vec3::sub(v, n.scalar_mul(2.0 * Vec3::dot_product(v, n)))
-    }
-
-    #[inline(always)]
-    pub fn mul( u:Vec3, v:Vec3 ) -> Vec3{
-        Vec3::new(u.x()*v.x(), u.y()*v.y(), u.z()*v.z())
+        v - n.scalar_mul(2.0 * Vec3::dot_product(v, n))
     }
 
     #[inline(always)]
     pub fn refract(uv: Vec3, n: Vec3, etai_over_etat: f64) -> Vec3 {
         let cos_theta = Vec3::dot_product(uv.scalar_mul(-1.0),n).min(1.0);
-        let r_out_perp = (Vec3::add(uv, n.scalar_mul(cos_theta))).scalar_mul(etai_over_etat);
-        let r_out_parallel = n.scalar_mul(-1.0 * ((1.0 - r_out_perp.length_squared()).abs()).sqrt());
-        Vec3::add(r_out_parallel, r_out_perp)
+        let r_out_perp = (uv + n.scalar_mul(cos_theta)).scalar_mul(etai_over_etat);
+        let r_out_parallel = n.scalar_mul(-((1.0 - r_out_perp.length_squared()).abs()).sqrt());
+        r_out_parallel + r_out_perp
     }
 
     pub fn random_in_unit_disk() -> Vec3{
@@ -172,3 +147,31 @@ impl Vec3 {
         }
     }
 }
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    #[inline(always)]
+    fn add(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.e[0] + v.e[0], self.e[1] + v.e[1], self.e[2] + v.e[2])
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    #[inline(always)]
+    fn sub(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.e[0] - v.e[0], self.e[1] - v.e[1], self.e[2] - v.e[2])
+    }
+}
+
+// Componentwise, as used for attenuating colours.
+impl Mul for Vec3 {
+    type Output = Vec3;
+
+    #[inline(always)]
+    fn mul(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.e[0] * v.e[0], self.e[1] * v.e[1], self.e[2] * v.e[2])
+    }
+}